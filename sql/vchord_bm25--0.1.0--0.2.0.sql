@@ -0,0 +1,488 @@
+-- Upgrade from 0.1.0. Run `SELECT bm25_upgrade_all_indexes();` afterwards, the indexes built by
+-- 0.1.0 can't be used until they are upgraded.
+
+-- bm25vector, it's compressed by PostgreSQL now
+
+ALTER TYPE bm25vector SET (STORAGE = extended);
+
+-- bm25query, the values stored by 0.1.0 get NULL in the new attributes
+
+ALTER TYPE bm25query
+    ADD ATTRIBUTE after_rank real,
+    ADD ATTRIBUTE after_ctid tid,
+    ADD ATTRIBUTE tokenizer text,
+    ADD ATTRIBUTE after_tableoid oid CASCADE;
+
+-- functions
+
+CREATE FUNCTION "_bm25catalog_bm25vector_add"(
+	"lhs" bm25vector,
+	"rhs" bm25vector
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', '_bm25catalog_bm25vector_add_wrapper';
+
+CREATE FUNCTION "_bm25catalog_bm25vector_to_jsonb"(
+	"vector" bm25vector
+) RETURNS jsonb
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', '_bm25catalog_bm25vector_to_jsonb_wrapper';
+
+CREATE FUNCTION "_bm25catalog_jsonb_to_bm25vector"(
+	"json" jsonb
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', '_bm25catalog_jsonb_to_bm25vector_wrapper';
+
+CREATE FUNCTION "bm25_index_check"(
+	"index" regclass,
+	"heapallindexed" bool DEFAULT false
+) RETURNS void
+STRICT VOLATILE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_index_check_wrapper';
+
+CREATE FUNCTION "bm25_index_format_version"(
+	"index" regclass
+) RETURNS INT
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_index_format_version_wrapper';
+
+CREATE FUNCTION "bm25_index_tokenizer"(
+	"index" regclass
+) RETURNS TEXT
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_index_tokenizer_wrapper';
+
+CREATE FUNCTION "bm25_metapage"(
+	"index" regclass
+) RETURNS TABLE (
+	"version" bigint,
+	"doc_cnt" bigint,
+	"doc_term_cnt" bigint,
+	"term_id_cnt" bigint,
+	"sealed_doc_id" bigint,
+	"current_doc_id" bigint,
+	"field_norm_blkno" bigint,
+	"payload_blkno" bigint,
+	"term_stat_blkno" bigint,
+	"delete_bitmap_blkno" bigint,
+	"growing_first_blkno" bigint,
+	"growing_last_blkno" bigint,
+	"growing_full_page_count" bigint,
+	"term_info_blkno" bigint,
+	"sealed_term_id_cnt" bigint,
+	"tokenizer" TEXT,
+	"tokenizer_version" bigint
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_metapage_wrapper';
+
+CREATE FUNCTION "bm25_normalize"(
+	"scores" real[],
+	"method" TEXT DEFAULT 'minmax'
+) RETURNS real[]
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_normalize_wrapper';
+
+CREATE FUNCTION "bm25_page_chain"(
+	"index" regclass,
+	"blkno" bigint
+) RETURNS TABLE (
+	"blkno" bigint,
+	"page_flag" TEXT,
+	"next_blkno" bigint,
+	"data_size" INT,
+	"free_size" INT
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_page_chain_wrapper';
+
+CREATE FUNCTION "bm25_page_data"(
+	"index" regclass,
+	"blkno" bigint
+) RETURNS bytea
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_page_data_wrapper';
+
+CREATE FUNCTION "bm25_page_header"(
+	"index" regclass,
+	"blkno" bigint
+) RETURNS TABLE (
+	"lsn" TEXT,
+	"checksum" INT,
+	"flags" INT,
+	"lower" INT,
+	"upper" INT,
+	"special" INT,
+	"pagesize" INT,
+	"version" INT,
+	"page_flag" TEXT,
+	"next_blkno" bigint,
+	"bm25_page_id" INT
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_page_header_wrapper';
+
+CREATE FUNCTION "bm25_postings"(
+	"index" regclass,
+	"term_id" bigint
+) RETURNS TABLE (
+	"doc_id" bigint,
+	"tf" bigint,
+	"ctid" tid,
+	"deleted" bool,
+	"block" INT,
+	"last_doc" bigint,
+	"blockwand_tf" bigint,
+	"blockwand_fieldnorm_id" INT,
+	"docid_bits" INT,
+	"tf_bits" INT
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_postings_wrapper';
+
+CREATE FUNCTION "bm25_query_max_score"(
+	"query" bm25query
+) RETURNS real
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_query_max_score_wrapper';
+
+CREATE FUNCTION "bm25_rrf"(
+	"index" regclass,
+	"query" bm25query,
+	"k" INT,
+	"other" tid[],
+	"rrf_k" INT DEFAULT 60
+) RETURNS TABLE (
+	"ctid" tid,
+	"score" real
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_rrf_wrapper';
+
+CREATE FUNCTION "bm25_score"(
+	"target_vector" bm25vector,
+	"query" bm25query
+) RETURNS real
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_score_wrapper';
+
+CREATE FUNCTION "bm25_score_normalized"(
+	"target_vector" bm25vector,
+	"query" bm25query
+) RETURNS real
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_score_normalized_wrapper';
+
+CREATE FUNCTION "bm25_search"(
+	"index" regclass,
+	"query" bm25query,
+	"k" INT
+) RETURNS TABLE (
+	"ctid" tid,
+	"score" real,
+	"doc_id" bigint
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_search_wrapper';
+
+CREATE FUNCTION "bm25_term_stats"(
+	"index" regclass
+) RETURNS TABLE (
+	"term_id" bigint,
+	"doc_freq" bigint,
+	"sealed_doc_cnt" bigint
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_term_stats_wrapper';
+
+CREATE FUNCTION "bm25_upgrade_all_indexes"() RETURNS SETOF TEXT
+STRICT VOLATILE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_upgrade_all_indexes_wrapper';
+
+CREATE FUNCTION bm25_upgrade_index(index regclass) RETURNS text
+VOLATILE STRICT LANGUAGE c AS 'MODULE_PATHNAME', 'bm25_upgrade_index_wrapper';
+
+CREATE FUNCTION "bm25_virtual_page_map"(
+	"index" regclass,
+	"blkno" bigint
+) RETURNS TABLE (
+	"blkno" bigint,
+	"kind" TEXT,
+	"virtual_id" bigint
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_virtual_page_map_wrapper';
+
+CREATE FUNCTION "bm25_weighted_fusion"(
+	"index" regclass,
+	"query" bm25query,
+	"k" INT,
+	"other" tid[],
+	"other_scores" real[],
+	"bm25_weight" real DEFAULT 0.5,
+	"normalization" TEXT DEFAULT 'minmax'
+) RETURNS TABLE (
+	"ctid" tid,
+	"score" real
+)
+STRICT VOLATILE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25_weighted_fusion_wrapper';
+
+CREATE FUNCTION "bm25vector_doc_len"(
+	"vector" bm25vector
+) RETURNS bigint
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_doc_len_wrapper';
+
+CREATE FUNCTION "bm25vector_filter"(
+	"vector" bm25vector,
+	"ids" bigint[],
+	"exclude" bool DEFAULT false
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_filter_wrapper';
+
+CREATE FUNCTION "bm25vector_indexes"(
+	"vector" bm25vector
+) RETURNS bigint[]
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_indexes_wrapper';
+
+CREATE FUNCTION "bm25vector_len"(
+	"vector" bm25vector
+) RETURNS bigint
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_len_wrapper';
+
+CREATE FUNCTION "bm25vector_top_n"(
+	"vector" bm25vector,
+	"n" INT
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_top_n_wrapper';
+
+CREATE FUNCTION "bm25vector_values"(
+	"vector" bm25vector
+) RETURNS bigint[]
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_values_wrapper';
+
+CREATE FUNCTION "search_bm25query_text"(
+	"target" TEXT,
+	"query" bm25query
+) RETURNS real
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'search_bm25query_text_wrapper';
+
+CREATE FUNCTION "to_bm25vector"(
+	"ids" bigint[],
+	"tfs" bigint[]
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'to_bm25vector_from_arrays_wrapper';
+
+CREATE FUNCTION "to_bm25vector"(
+	"ids" bigint[]
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'to_bm25vector_from_ids_wrapper';
+
+CREATE FUNCTION "unicode_tokenizer_split"(
+	"text" TEXT,
+	"tokenizer_name" TEXT
+) RETURNS TEXT[]
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'unicode_tokenizer_split_with_wrapper';
+
+CREATE FUNCTION "tokenize"(
+	"content" TEXT,
+	"tokenizer_name" TEXT,
+	"version" INT
+) RETURNS bm25vector
+IMMUTABLE STRICT PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'tokenize_versioned_wrapper';
+
+CREATE FUNCTION "create_tokenizer"(
+	"tokenizer_name" TEXT,
+	"config_str" TEXT,
+	"definition" TEXT
+) RETURNS void
+STRICT
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'create_tokenizer_with_definition_wrapper';
+
+CREATE FUNCTION "bm25vector_to_tokens"(
+	"vector" bm25vector,
+	"tokenizer_name" TEXT
+) RETURNS TABLE (
+	"id" bigint,
+	"token" TEXT,
+	"tf" bigint
+)
+STRICT STABLE PARALLEL SAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'bm25vector_to_tokens_wrapper';
+
+CREATE FUNCTION "tokenize_and_grow"(
+	"content" TEXT,
+	"tokenizer_name" TEXT
+) RETURNS bm25vector
+STRICT VOLATILE PARALLEL UNSAFE
+LANGUAGE c
+AS 'MODULE_PATHNAME', 'tokenize_and_grow_wrapper';
+
+CREATE FUNCTION "tokenizer_catalog_invalidate_trigger"()
+	RETURNS TRIGGER
+	LANGUAGE c
+	AS 'MODULE_PATHNAME', 'tokenizer_catalog_invalidate_trigger_wrapper';
+
+CREATE FUNCTION "tokenizer_vocab_invalidate_trigger"()
+	RETURNS TRIGGER
+	LANGUAGE c
+	AS 'MODULE_PATHNAME', 'tokenizer_vocab_invalidate_trigger_wrapper';
+
+CREATE OR REPLACE FUNCTION to_bm25query(index_oid regclass, query_str text, tokenizer_name text) RETURNS bm25query
+    STABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT index_oid, tokenize(query_str, tokenizer_name), NULL::real, NULL::tid, tokenizer_name, NULL::oid;
+    $$;
+
+CREATE FUNCTION to_bm25query(index_oid regclass, query_str text) RETURNS bm25query
+    STABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT to_bm25query(index_oid, query_str, bm25_index_tokenizer(index_oid));
+    $$;
+
+-- it replaces `ROW(index_oid, query_vector)::bm25query` of 0.1.0
+CREATE FUNCTION to_bm25query(index_oid regclass, query_vector bm25vector) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT index_oid, query_vector, NULL::real, NULL::tid, NULL::text, NULL::oid;
+    $$;
+
+CREATE FUNCTION bm25query_search_after(query bm25query, rank real, ctid tid) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT (query).index_oid, (query).query_vector, rank, ctid, (query).tokenizer, NULL::oid;
+    $$;
+
+CREATE FUNCTION bm25query_search_after(query bm25query, rank real, ctid tid, tableoid oid) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT (query).index_oid, (query).query_vector, rank, ctid, (query).tokenizer, tableoid;
+    $$;
+
+-- tokenizer catalog
+
+ALTER TABLE bm25_catalog.tokenizers ADD COLUMN version INTEGER;
+
+CREATE TABLE bm25_catalog.tokenizer_versions (
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    fingerprint TEXT NOT NULL,
+    PRIMARY KEY (name, version)
+);
+
+-- The configs of the existing tokenizers were not recorded, they're at version 1. An empty
+-- fingerprint never matches, so a tokenizer created again after it's dropped gets version 2.
+INSERT INTO bm25_catalog.tokenizer_versions (name, version, fingerprint)
+SELECT name, 1, '' FROM bm25_catalog.tokenizers;
+
+CREATE TABLE bm25_catalog.huggingface_tokenizers (
+    name TEXT NOT NULL UNIQUE PRIMARY KEY REFERENCES bm25_catalog.tokenizers (name) ON DELETE CASCADE,
+    definition TEXT NOT NULL
+);
+
+CREATE OR REPLACE FUNCTION unicode_tokenizer_insert_trigger()
+RETURNS TRIGGER AS $$
+DECLARE
+    tokenizer_name TEXT := TG_ARGV[0];
+    target_column TEXT := TG_ARGV[1];
+BEGIN
+    EXECUTE format('
+    WITH new_tokens AS (
+        SELECT unnest(unicode_tokenizer_split($1.%I, %L)) AS token
+    ),
+    to_insert AS (
+        SELECT token FROM new_tokens
+        WHERE NOT EXISTS (
+            SELECT 1 FROM bm25_catalog.%I WHERE token = new_tokens.token
+        )
+    )
+    INSERT INTO bm25_catalog.%I (token) SELECT token FROM to_insert ON CONFLICT (token) DO NOTHING', target_column, tokenizer_name, tokenizer_name, tokenizer_name) USING NEW;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tokenizers_invalidate_trigger
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog.tokenizers
+FOR EACH STATEMENT EXECUTE FUNCTION tokenizer_catalog_invalidate_trigger();
+
+CREATE TRIGGER huggingface_tokenizers_invalidate_trigger
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog.huggingface_tokenizers
+FOR EACH STATEMENT EXECUTE FUNCTION tokenizer_catalog_invalidate_trigger();
+
+-- the vocabulary tables of the existing Unicode tokenizers
+DO $$
+DECLARE
+    tokenizer_name TEXT;
+BEGIN
+    FOR tokenizer_name IN
+        SELECT name FROM bm25_catalog.tokenizers
+        WHERE to_regclass(format('bm25_catalog.%I', name)) IS NOT NULL
+    LOOP
+        EXECUTE format('CREATE TRIGGER %I AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog.%I FOR EACH STATEMENT EXECUTE FUNCTION bm25_catalog.tokenizer_vocab_invalidate_trigger()', tokenizer_name || '_invalidate_trigger', tokenizer_name);
+    END LOOP;
+END;
+$$;
+
+-- operators
+
+CREATE OPERATOR pg_catalog.<&> (
+    PROCEDURE = search_bm25query_text,
+    LEFTARG = text,
+    RIGHTARG = bm25query
+);
+
+CREATE OPERATOR pg_catalog.+ (
+    PROCEDURE = _bm25catalog_bm25vector_add,
+    LEFTARG = bm25vector,
+    RIGHTARG = bm25vector,
+    COMMUTATOR = +
+);
+
+CREATE CAST (bm25vector AS jsonb) WITH FUNCTION _bm25catalog_bm25vector_to_jsonb(bm25vector);
+CREATE CAST (jsonb AS bm25vector) WITH FUNCTION _bm25catalog_jsonb_to_bm25vector(jsonb);
+
+CREATE OPERATOR FAMILY text_bm25_ops USING bm25;
+
+CREATE OPERATOR CLASS text_bm25_ops FOR TYPE text USING bm25 FAMILY text_bm25_ops AS
+    OPERATOR 1 pg_catalog.<&>(text, bm25query) FOR ORDER BY float_ops;
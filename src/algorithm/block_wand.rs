@@ -6,11 +6,12 @@ use crate::{
         field_norm::{id_to_fieldnorm, FieldNormRead, FieldNormReader},
         posting::{PostingReader, TERMINATED_DOC},
     },
-    utils::topk_computer::TopKComputer,
+    utils::collector::Collector,
     weight::Bm25Weight,
 };
 
 pub struct SealedScorer {
+    pub term_id: u32,
     pub posting: PostingReader<true>,
     pub weight: Bm25Weight,
     pub max_score: f32,
@@ -53,7 +54,7 @@ pub fn block_wand_single(
     mut scorer: SealedScorer,
     fieldnorm_reader: &FieldNormReader,
     delete_bitmap_reader: &DeleteBitmapReader,
-    computer: &mut TopKComputer,
+    computer: &mut impl Collector,
 ) {
     'outer: loop {
         while scorer.posting.block_max_score(&scorer.weight) <= computer.threshold() {
@@ -85,12 +86,13 @@ pub fn block_wand(
     mut scorers: Vec<SealedScorer>,
     fieldnorm_reader: &FieldNormReader,
     delete_bitmap_reader: &DeleteBitmapReader,
-    computer: &mut TopKComputer,
+    computer: &mut impl Collector,
 ) {
     for s in &mut scorers {
         s.posting.decode_block();
     }
     scorers.sort_by_key(|s| s.posting.doc_id());
+    let mut term_scores = Vec::with_capacity(scorers.len());
 
     while let Some((before_pivot_len, pivot_len, pivot_doc)) =
         find_pivot_doc(&scorers, computer.threshold())
@@ -114,10 +116,14 @@ pub fn block_wand(
 
         if !delete_bitmap_reader.is_delete(pivot_doc) {
             let len = id_to_fieldnorm(fieldnorm_reader.read(pivot_doc));
-            let score = scorers[..pivot_len]
-                .iter()
-                .map(|scorer| scorer.weight.score(len, scorer.posting.term_freq()))
-                .sum();
+            // sum in the order of term id, so the score is bitwise identical to `bm25_score_batch`
+            term_scores.clear();
+            term_scores.extend(scorers[..pivot_len].iter().map(|scorer| {
+                let score = scorer.weight.score(len, scorer.posting.term_freq());
+                (scorer.term_id, score)
+            }));
+            term_scores.sort_unstable_by_key(|&(term_id, _)| term_id);
+            let score = term_scores.iter().map(|&(_, score)| score).sum();
             computer.push(score, pivot_doc);
         }
 
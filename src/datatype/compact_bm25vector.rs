@@ -0,0 +1,113 @@
+// The compact layout of bm25vector, used by the large values stored in tables and the items of
+// the growing segment. The small ones keep the uncompressed layout of `Bm25VectorHeader`.
+//
+// varlena: u32
+// len: u32, with `COMPACT_FLAG` set
+// terms: the delta of each term id from the previous one as a vint, followed by its tf in a
+//        byte if it's less than `TF_ESCAPE`, otherwise `TF_ESCAPE` and the tf as a vint
+//
+// `doc_len` is not stored, it's the sum of the tfs.
+
+use crate::utils::vint::{decode_vint32, encode_vint32};
+
+use super::bm25vector::Bm25VectorBorrowed;
+
+// `len` of the uncompressed layout never reaches it, since a varlena is at most 1GB
+const COMPACT_FLAG: u32 = 1 << 31;
+const HEADER_SIZE: usize = 8;
+const TF_ESCAPE: u8 = u8::MAX;
+
+pub fn encode_compact(vector: Bm25VectorBorrowed) -> Vec<u8> {
+    assert!(vector.len() < COMPACT_FLAG);
+    let mut buf = vec![0u8; HEADER_SIZE];
+    let mut last = 0;
+    for (&index, &value) in vector.indexes().iter().zip(vector.values()) {
+        encode_vint32(index - last, &mut buf).unwrap();
+        last = index;
+        if value < TF_ESCAPE as u32 {
+            buf.push(value as u8);
+        } else {
+            buf.push(TF_ESCAPE);
+            encode_vint32(value, &mut buf).unwrap();
+        }
+    }
+    let varlena = (buf.len() << 2) as u32;
+    buf[..4].copy_from_slice(&varlena.to_ne_bytes());
+    buf[4..HEADER_SIZE].copy_from_slice(&(vector.len() | COMPACT_FLAG).to_ne_bytes());
+    buf
+}
+
+/// Return the term count of the vector at `ptr`, or `None` if it's in the uncompressed layout.
+///
+/// # Safety
+///
+/// `ptr` points to a detoasted bm25vector with a 4-byte varlena header.
+pub unsafe fn compact_len(ptr: *const u8) -> Option<u32> {
+    // the header is read as bytes, as a compact vector may be shorter than `Bm25VectorHeader`
+    let header = unsafe { std::slice::from_raw_parts(ptr, HEADER_SIZE) };
+    let len = u32::from_ne_bytes(header[4..].try_into().unwrap());
+    (len & COMPACT_FLAG != 0).then_some(len & !COMPACT_FLAG)
+}
+
+/// Decode the vector at `ptr` into `indexes` and `values` and return its `doc_len`,
+/// or `None` if it's in the uncompressed layout.
+///
+/// # Safety
+///
+/// `ptr` points to a detoasted bm25vector with a 4-byte varlena header.
+pub unsafe fn decode_compact(
+    ptr: *const u8,
+    indexes: &mut Vec<u32>,
+    values: &mut Vec<u32>,
+) -> Option<u32> {
+    let len = unsafe { compact_len(ptr) }? as usize;
+    indexes.clear();
+    values.clear();
+    indexes.resize(len, 0);
+    values.resize(len, 0);
+    Some(unsafe { decode_compact_into(ptr, indexes, values) })
+}
+
+/// Decode the vector at `ptr` into `indexes` and `values`, whose length is its term count,
+/// and return its `doc_len`.
+///
+/// # Safety
+///
+/// `ptr` points to a detoasted bm25vector in the compact layout with a 4-byte varlena header.
+pub unsafe fn decode_compact_into(ptr: *const u8, indexes: &mut [u32], values: &mut [u32]) -> u32 {
+    let header = unsafe { std::slice::from_raw_parts(ptr, HEADER_SIZE) };
+    let varlena = u32::from_ne_bytes(header[..4].try_into().unwrap());
+    let size = varlena as usize >> 2;
+    // every term takes at least 2 bytes, so a size that can't hold them is a corrupt header
+    if size < HEADER_SIZE || (size - HEADER_SIZE) / 2 < indexes.len() {
+        pgrx::error!("detect data corruption");
+    }
+    let mut data = unsafe { std::slice::from_raw_parts(ptr.add(HEADER_SIZE), size - HEADER_SIZE) };
+
+    let mut last = 0u32;
+    let mut doc_len = 0u32;
+    for (i, (index, value)) in indexes.iter_mut().zip(values.iter_mut()).enumerate() {
+        let delta = decode_vint32(&mut data);
+        // term ids are strictly increasing
+        let Some(next) = last.checked_add(delta).filter(|_| i == 0 || delta != 0) else {
+            pgrx::error!("detect data corruption");
+        };
+        last = next;
+        let Some((&byte, rest)) = data.split_first() else {
+            pgrx::error!("detect data corruption");
+        };
+        data = rest;
+        *value = match byte {
+            TF_ESCAPE => decode_vint32(&mut data),
+            value => value as u32,
+        };
+        *index = last;
+        doc_len = doc_len
+            .checked_add(*value)
+            .unwrap_or_else(|| pgrx::error!("detect data corruption"));
+    }
+    if !data.is_empty() {
+        pgrx::error!("detect data corruption");
+    }
+    doc_len
+}
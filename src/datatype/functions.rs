@@ -1,18 +1,22 @@
 use std::num::NonZero;
 
+use pgrx::{heap_tuple::PgHeapTuple, AllocatedByRust};
+
 use crate::{
-    page::{page_read, METAPAGE_BLKNO},
-    segment::{meta::MetaPageData, term_stat::TermStatReader},
-    weight::bm25_score_batch,
+    index::Bm25Stats,
+    weight::{bm25_max_score, bm25_score_batch},
 };
 
-use super::memory_bm25vector::{Bm25VectorInput, Bm25VectorOutput};
+use super::{
+    bm25vector::Bm25VectorBorrowed,
+    memory_bm25vector::{Bm25VectorInput, Bm25VectorOutput},
+};
 
-#[pgrx::pg_extern(stable, strict, parallel_safe)]
-pub fn search_bm25query(
-    target_vector: Bm25VectorInput,
-    query: pgrx::composite_type!("bm25query"),
-) -> f32 {
+// run `f` with the query vector and the statistics of the index the query is built for
+fn with_query<R>(
+    query: &PgHeapTuple<'_, AllocatedByRust>,
+    f: impl FnOnce(&Bm25Stats, Bm25VectorBorrowed) -> R,
+) -> R {
     let index_oid: pgrx::pg_sys::Oid = query
         .get_by_index(NonZero::new(1).unwrap())
         .unwrap()
@@ -21,25 +25,75 @@ pub fn search_bm25query(
         .get_by_index(NonZero::new(2).unwrap())
         .unwrap()
         .unwrap();
-    let query_vector = query_vector.borrow();
-    let target_vector = target_vector.borrow();
+    let tokenizer: Option<String> = query.get_by_index(NonZero::new(5).unwrap()).unwrap();
+
+    let stats = Bm25Stats::of_query(index_oid, tokenizer.as_deref(), query_vector.borrow());
+    f(&stats, query_vector.borrow())
+}
 
+fn score(target_vector: Bm25VectorBorrowed, query: &PgHeapTuple<'_, AllocatedByRust>) -> f32 {
+    with_query(query, |stats, query_vector| {
+        bm25_score_batch(
+            stats.doc_cnt,
+            stats.avgdl(),
+            stats,
+            target_vector,
+            query_vector,
+        )
+    })
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn search_bm25query(
+    target_vector: Bm25VectorInput,
+    query: pgrx::composite_type!("bm25query"),
+) -> f32 {
+    -score(target_vector.borrow(), &query)
+}
+
+// the text is tokenized by the tokenizer of the index the query is built for, see `text_bm25_ops`
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn search_bm25query_text(target: &str, query: pgrx::composite_type!("bm25query")) -> f32 {
+    let index_oid: pgrx::pg_sys::Oid = query
+        .get_by_index(NonZero::new(1).unwrap())
+        .unwrap()
+        .unwrap();
     let index =
         unsafe { pgrx::PgRelation::with_lock(index_oid, pgrx::pg_sys::AccessShareLock as _) };
-    let meta = {
-        let page = page_read(index.as_ptr(), METAPAGE_BLKNO);
-        unsafe { &*(page.data().as_ptr() as *const MetaPageData) }
-    };
-
-    let term_stat_reader = TermStatReader::new(index.as_ptr(), meta);
-    let avgdl = meta.avgdl();
-    let scores = bm25_score_batch(
-        meta.doc_cnt,
-        avgdl,
-        &term_stat_reader,
-        target_vector,
-        query_vector,
-    );
-
-    scores * -1.0
+    let tokenizer = crate::index::index_tokenizer(index.as_ptr());
+    let target_vector = crate::token::tokenize(target, &tokenizer);
+    -score(target_vector.borrow(), &query)
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn bm25_score(
+    target_vector: Bm25VectorInput,
+    query: pgrx::composite_type!("bm25query"),
+) -> f32 {
+    score(target_vector.borrow(), &query)
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn bm25_query_max_score(query: pgrx::composite_type!("bm25query")) -> f32 {
+    with_query(&query, |stats, query_vector| {
+        bm25_max_score(stats.doc_cnt, stats.avgdl(), stats, query_vector)
+    })
+}
+
+// the score divided by the max score of the query, so it's comparable across queries
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn bm25_score_normalized(
+    target_vector: Bm25VectorInput,
+    query: pgrx::composite_type!("bm25query"),
+) -> f32 {
+    let target_vector = target_vector.borrow();
+    with_query(&query, |stats, query_vector| {
+        let avgdl = stats.avgdl();
+        let max_score = bm25_max_score(stats.doc_cnt, avgdl, stats, query_vector);
+        if max_score == 0.0 {
+            return 0.0;
+        }
+        let score = bm25_score_batch(stats.doc_cnt, avgdl, stats, target_vector, query_vector);
+        score / max_score
+    })
 }
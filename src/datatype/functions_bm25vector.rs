@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use pgrx::JsonB;
+
+use super::{
+    bm25vector::Bm25VectorBorrowed,
+    memory_bm25vector::{Bm25VectorInput, Bm25VectorOutput},
+};
+
+fn term_id(id: i64) -> u32 {
+    u32::try_from(id).unwrap_or_else(|_| pgrx::error!("term id {} is out of range", id))
+}
+
+fn term_frequency(tf: i64) -> u32 {
+    u32::try_from(tf).unwrap_or_else(|_| pgrx::error!("term frequency {} is out of range", tf))
+}
+
+// every occurrence of an id counts as 1
+#[pgrx::pg_extern(name = "to_bm25vector", immutable, strict, parallel_safe)]
+pub fn to_bm25vector_from_ids(ids: Vec<i64>) -> Bm25VectorOutput {
+    let ids = ids.into_iter().map(term_id).collect::<Vec<_>>();
+    Bm25VectorOutput::from_ids(&ids)
+}
+
+#[pgrx::pg_extern(name = "to_bm25vector", immutable, strict, parallel_safe)]
+pub fn to_bm25vector_from_arrays(ids: Vec<i64>, tfs: Vec<i64>) -> Bm25VectorOutput {
+    if ids.len() != tfs.len() {
+        pgrx::error!(
+            "ids and tfs must have the same length, got {} and {}",
+            ids.len(),
+            tfs.len()
+        );
+    }
+    Bm25VectorOutput::from_pairs(
+        ids.into_iter()
+            .map(term_id)
+            .zip(tfs.into_iter().map(term_frequency)),
+    )
+}
+
+// the term frequencies of the terms in both vectors are summed
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn _bm25catalog_bm25vector_add(lhs: Bm25VectorInput, rhs: Bm25VectorInput) -> Bm25VectorOutput {
+    let (lhs, rhs) = (lhs.borrow(), rhs.borrow());
+    Bm25VectorOutput::from_pairs(pairs(&lhs).chain(pairs(&rhs)))
+}
+
+// keep the given terms, or drop them with `exclude`
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_filter(
+    vector: Bm25VectorInput,
+    ids: Vec<i64>,
+    exclude: pgrx::default!(bool, false),
+) -> Bm25VectorOutput {
+    let ids = ids.into_iter().map(term_id).collect::<HashSet<_>>();
+    let vector = vector.borrow();
+    Bm25VectorOutput::from_pairs(pairs(&vector).filter(|(id, _)| ids.contains(id) != exclude))
+}
+
+// keep the `n` terms with the highest term frequencies, ties are broken by the smaller id
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_top_n(vector: Bm25VectorInput, n: i32) -> Bm25VectorOutput {
+    let n =
+        usize::try_from(n).unwrap_or_else(|_| pgrx::error!("n must be non-negative, got {}", n));
+    let vector = vector.borrow();
+    let mut terms = pairs(&vector).collect::<Vec<_>>();
+    terms.sort_by_key(|&(id, tf)| (std::cmp::Reverse(tf), id));
+    terms.truncate(n);
+    Bm25VectorOutput::from_pairs(terms)
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_len(vector: Bm25VectorInput) -> i64 {
+    vector.borrow().len() as i64
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_doc_len(vector: Bm25VectorInput) -> i64 {
+    vector.borrow().doc_len() as i64
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_indexes(vector: Bm25VectorInput) -> Vec<i64> {
+    vector
+        .borrow()
+        .indexes()
+        .iter()
+        .map(|&x| x as i64)
+        .collect()
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25vector_values(vector: Bm25VectorInput) -> Vec<i64> {
+    vector.borrow().values().iter().map(|&x| x as i64).collect()
+}
+
+// '{1:2, 3:1}' is converted to '{"1": 2, "3": 1}', as the keys of a json object are strings
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn _bm25catalog_bm25vector_to_jsonb(vector: Bm25VectorInput) -> JsonB {
+    let vector = vector.borrow();
+    let object = pairs(&vector)
+        .map(|(id, tf)| (id.to_string(), serde_json::Value::from(tf)))
+        .collect::<serde_json::Map<_, _>>();
+    JsonB(serde_json::Value::Object(object))
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn _bm25catalog_jsonb_to_bm25vector(json: JsonB) -> Bm25VectorOutput {
+    let serde_json::Value::Object(object) = json.0 else {
+        pgrx::error!("bm25vector must be a jsonb object of term ids and term frequencies");
+    };
+    let terms = object
+        .into_iter()
+        .map(|(id, tf)| {
+            let id = id
+                .parse::<u32>()
+                .unwrap_or_else(|_| pgrx::error!("term id \"{}\" is not a valid u32", id));
+            let tf = tf
+                .as_u64()
+                .and_then(|tf| u32::try_from(tf).ok())
+                .unwrap_or_else(|| pgrx::error!("term frequency {} is not a valid u32", tf));
+            (id, tf)
+        })
+        .collect::<Vec<_>>();
+    Bm25VectorOutput::from_pairs(terms)
+}
+
+fn pairs<'a>(vector: &'a Bm25VectorBorrowed) -> impl Iterator<Item = (u32, u32)> + 'a {
+    vector
+        .indexes()
+        .iter()
+        .copied()
+        .zip(vector.values().iter().copied())
+}
@@ -1,5 +1,5 @@
 use std::collections::BTreeMap;
-use std::{alloc::Layout, ops::Deref, ptr::NonNull};
+use std::{alloc::Layout, borrow::Cow, ops::Deref, ptr::NonNull};
 
 use pgrx::{
     pg_sys::{Datum, Oid},
@@ -9,7 +9,11 @@ use pgrx::{
     FromDatum, IntoDatum,
 };
 
-use super::bm25vector::Bm25VectorBorrowed;
+use super::{bm25vector::Bm25VectorBorrowed, compact_bm25vector};
+
+// Vectors up to this size in the uncompressed layout are stored in it, so they're read in place.
+// The larger ones, which take most of the space, are stored in the compact layout.
+const COMPACT_THRESHOLD: usize = 256;
 
 #[repr(C, align(8))]
 pub struct Bm25VectorHeader {
@@ -31,6 +35,16 @@ impl Bm25VectorHeader {
         let layout = layout.extend(layout1).unwrap().0.pad_to_align();
         layout.extend(layout2).unwrap().0.pad_to_align()
     }
+    fn size(&self) -> usize {
+        (self.varlena >> 2) as usize
+    }
+    // A vector in the uncompressed layout has exactly the size of its `len`, while a corrupt
+    // compact vector that lost its flag almost never has, so it's not misread.
+    pub fn check(&self) {
+        if self.size() != Self::layout(self.len).size() {
+            pgrx::error!("detect data corruption");
+        }
+    }
     fn indexes(&self) -> &[u32] {
         let ptr = self.phantom.as_ptr().cast();
         unsafe { std::slice::from_raw_parts(ptr, self.len as usize) }
@@ -44,13 +58,29 @@ impl Bm25VectorHeader {
             std::slice::from_raw_parts(ptr, len)
         }
     }
-    pub fn borrow(&self) -> Bm25VectorBorrowed {
+    fn terms_mut(&mut self) -> (&mut [u32], &mut [u32]) {
+        let len = self.len as usize;
+        unsafe {
+            let indexes = self.phantom.as_mut_ptr().cast::<u32>();
+            let values = indexes.add(len);
+            let values = values.add(values.align_offset(8));
+            (
+                std::slice::from_raw_parts_mut(indexes, len),
+                std::slice::from_raw_parts_mut(values, len),
+            )
+        }
+    }
+    pub fn borrow(&self) -> Bm25VectorBorrowed<'_> {
         unsafe { Bm25VectorBorrowed::new_unchecked(self.doc_len, self.indexes(), self.values()) }
     }
-    pub fn to_bytes(&self) -> &[u8] {
-        let len = self.varlena as usize >> 2;
-        let ptr = self as *const Bm25VectorHeader as *const u8;
-        unsafe { std::slice::from_raw_parts(ptr, len) }
+    // the bytes stored in tables and the growing segment
+    pub fn encode(&self) -> Cow<'_, [u8]> {
+        if self.size() <= COMPACT_THRESHOLD {
+            let ptr = (self as *const Self).cast();
+            Cow::Borrowed(unsafe { std::slice::from_raw_parts(ptr, self.size()) })
+        } else {
+            Cow::Owned(compact_bm25vector::encode_compact(self.borrow()))
+        }
     }
 }
 
@@ -61,13 +91,51 @@ pub enum Bm25VectorInput<'a> {
 
 impl Bm25VectorInput<'_> {
     unsafe fn new(p: NonNull<Bm25VectorHeader>) -> Self {
-        let q = unsafe {
-            NonNull::new(pgrx::pg_sys::pg_detoast_datum(p.cast().as_ptr()).cast()).unwrap()
-        };
-        if p != q {
-            Bm25VectorInput::Owned(Bm25VectorOutput(q))
-        } else {
-            unsafe { Bm25VectorInput::Borrowed(p.as_ref()) }
+        match unsafe { Detoasted::new(p) } {
+            Detoasted::Owned(vector) => Bm25VectorInput::Owned(vector),
+            Detoasted::InPlace(p) => unsafe { Bm25VectorInput::Borrowed(p.as_ref()) },
+        }
+    }
+}
+
+enum Detoasted {
+    // the datum is in the uncompressed layout and not toasted, so it's read in place
+    InPlace(NonNull<Bm25VectorHeader>),
+    // the datum is copied out of the toast, or decoded from the compact layout
+    Owned(Bm25VectorOutput),
+}
+
+impl Detoasted {
+    // A compact datum is decoded on every read, since its delta encoded term ids can't be
+    // borrowed as the slices of `Bm25VectorBorrowed`. It's decoded straight into a vector in
+    // the uncompressed layout, which costs one allocation as a toasted datum does. Only the
+    // vectors above `COMPACT_THRESHOLD` are compact, so the small ones are still read in place.
+    unsafe fn new(p: NonNull<Bm25VectorHeader>) -> Self {
+        unsafe {
+            let q: NonNull<Bm25VectorHeader> =
+                NonNull::new(pgrx::pg_sys::pg_detoast_datum(p.cast().as_ptr()).cast()).unwrap();
+            if let Some(len) = compact_bm25vector::compact_len(q.as_ptr().cast()) {
+                let vector = Bm25VectorOutput::new_with(len, |indexes, values| {
+                    compact_bm25vector::decode_compact_into(q.as_ptr().cast(), indexes, values)
+                });
+                if p != q {
+                    pgrx::pg_sys::pfree(q.as_ptr() as _);
+                }
+                return Detoasted::Owned(vector);
+            }
+            q.as_ref().check();
+            if p != q {
+                Detoasted::Owned(Bm25VectorOutput(q))
+            } else {
+                Detoasted::InPlace(p)
+            }
+        }
+    }
+
+    fn into_owned(self) -> Bm25VectorOutput {
+        match self {
+            Detoasted::Owned(vector) => vector,
+            Detoasted::InPlace(p) => Bm25VectorOutput::new(unsafe { p.as_ref() }.borrow()),
         }
     }
 }
@@ -87,47 +155,70 @@ pub struct Bm25VectorOutput(NonNull<Bm25VectorHeader>);
 
 impl Bm25VectorOutput {
     pub fn new(vector: Bm25VectorBorrowed) -> Self {
-        let len = vector.len();
+        Self::new_with(vector.len(), |indexes, values| {
+            indexes.copy_from_slice(vector.indexes());
+            values.copy_from_slice(vector.values());
+            vector.doc_len()
+        })
+    }
+
+    // `f` fills the term ids and frequencies of `len` terms, and returns the `doc_len`
+    fn new_with(len: u32, f: impl FnOnce(&mut [u32], &mut [u32]) -> u32) -> Self {
         unsafe {
             let layout = Bm25VectorHeader::layout(len);
-            let ptr = pgrx::pg_sys::palloc(layout.size()) as *mut Bm25VectorHeader;
-            ptr.cast::<u8>().add(layout.size() - 8).write_bytes(0, 8);
+            let ptr = pgrx::pg_sys::palloc0(layout.size()) as *mut Bm25VectorHeader;
             (&raw mut (*ptr).varlena).write(Bm25VectorHeader::varlena(layout.size()));
             (&raw mut (*ptr).len).write(len);
-            (&raw mut (*ptr).doc_len).write(vector.doc_len());
-            (&raw mut (*ptr).reserved).write(0);
-            let mut data_ptr = (*ptr).phantom.as_mut_ptr().cast::<u32>();
-            std::ptr::copy_nonoverlapping(vector.indexes().as_ptr(), data_ptr, len as usize);
-            data_ptr = data_ptr.add(len as usize);
-            let offset = data_ptr.align_offset(8);
-            std::ptr::write_bytes(data_ptr, 0, offset);
-            data_ptr = data_ptr.add(offset);
-            std::ptr::copy_nonoverlapping(vector.values().as_ptr(), data_ptr, len as usize);
+            let (indexes, values) = (*ptr).terms_mut();
+            let doc_len = f(indexes, values);
+            (&raw mut (*ptr).doc_len).write(doc_len);
             Bm25VectorOutput(NonNull::new(ptr).unwrap())
         }
     }
 
     pub fn from_ids(ids: &[u32]) -> Self {
+        Self::from_pairs(ids.iter().map(|&id| (id, 1)))
+    }
+
+    // the term frequencies of the same term are summed, and terms with zero frequency are dropped
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (u32, u32)>) -> Self {
         let mut map: BTreeMap<u32, u32> = BTreeMap::new();
-        for term_id in ids {
-            *map.entry(*term_id).or_insert(0) += 1;
+        for (term_id, tf) in pairs {
+            let value = map.entry(term_id).or_insert(0);
+            *value = value
+                .checked_add(tf)
+                .unwrap_or_else(|| pgrx::error!("term frequency out of range"));
         }
         let mut doc_len: u32 = 0;
         let mut indexes = Vec::with_capacity(map.len());
         let mut values = Vec::with_capacity(map.len());
         for (index, value) in map {
+            if value == 0 {
+                continue;
+            }
             indexes.push(index);
             values.push(value);
-            doc_len = doc_len.checked_add(value).expect("overflow");
+            doc_len = doc_len
+                .checked_add(value)
+                .unwrap_or_else(|| pgrx::error!("document length out of range"));
         }
         let vector = unsafe { Bm25VectorBorrowed::new_unchecked(doc_len, &indexes, &values) };
         Self::new(vector)
     }
 
-    pub fn into_raw(self) -> *mut Bm25VectorHeader {
-        let result = self.0.as_ptr();
-        std::mem::forget(self);
-        result
+    // the datum is in the layout chosen by `Bm25VectorHeader::encode`
+    pub fn into_raw(self) -> *mut u8 {
+        if self.size() <= COMPACT_THRESHOLD {
+            let ptr = self.0.as_ptr().cast();
+            std::mem::forget(self);
+            return ptr;
+        }
+        let bytes = compact_bm25vector::encode_compact(self.borrow());
+        unsafe {
+            let ptr = pgrx::pg_sys::palloc(bytes.len()) as *mut u8;
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            ptr
+        }
     }
 }
 
@@ -160,7 +251,7 @@ impl FromDatum for Bm25VectorInput<'_> {
 
 impl IntoDatum for Bm25VectorOutput {
     fn into_datum(self) -> Option<Datum> {
-        Some(Datum::from(self.into_raw() as *mut ()))
+        Some(Datum::from(self.into_raw()))
     }
 
     fn type_oid() -> Oid {
@@ -174,14 +265,7 @@ impl FromDatum for Bm25VectorOutput {
             None
         } else {
             let p = NonNull::new(datum.cast_mut_ptr::<Bm25VectorHeader>())?;
-            let q =
-                unsafe { NonNull::new(pgrx::pg_sys::pg_detoast_datum(p.cast().as_ptr()).cast())? };
-            if p != q {
-                Some(Bm25VectorOutput(q))
-            } else {
-                let vector = p.as_ref().borrow();
-                Some(Bm25VectorOutput::new(vector))
-            }
+            Some(unsafe { Detoasted::new(p) }.into_owned())
         }
     }
 }
@@ -194,15 +278,7 @@ unsafe impl pgrx::datum::UnboxDatum for Bm25VectorOutput {
         Self: 'src,
     {
         let p = NonNull::new(d.sans_lifetime().cast_mut_ptr::<Bm25VectorHeader>()).unwrap();
-        let q = unsafe {
-            NonNull::new(pgrx::pg_sys::pg_detoast_datum(p.cast().as_ptr()).cast()).unwrap()
-        };
-        if p != q {
-            Bm25VectorOutput(q)
-        } else {
-            let vector = p.as_ref().borrow();
-            Bm25VectorOutput::new(vector)
-        }
+        unsafe { Detoasted::new(p) }.into_owned()
     }
 }
 
@@ -235,6 +311,6 @@ unsafe impl pgrx::callconv::BoxRet for Bm25VectorOutput {
         self,
         fcinfo: &mut pgrx::callconv::FcInfo<'fcx>,
     ) -> pgrx::datum::Datum<'fcx> {
-        unsafe { fcinfo.return_raw_datum(Datum::from(self.into_raw() as *mut ())) }
+        unsafe { fcinfo.return_raw_datum(Datum::from(self.into_raw())) }
     }
 }
@@ -1,9 +1,12 @@
 mod binary_bm25vector;
 mod bm25vector;
 mod bytea;
+mod compact_bm25vector;
 mod functions;
+mod functions_bm25vector;
 mod memory_bm25vector;
 mod text_bm25vector;
 
 pub use bm25vector::Bm25VectorBorrowed;
+pub use compact_bm25vector::decode_compact;
 pub use memory_bm25vector::{Bm25VectorHeader, Bm25VectorInput, Bm25VectorOutput};
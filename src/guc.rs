@@ -1,6 +1,9 @@
 use pgrx::{GucContext, GucFlags, GucRegistry, GucSetting};
 
 pub static BM25_LIMIT: GucSetting<i32> = GucSetting::<i32>::new(100);
+// the maximum of `bm25_limit`, also the maximum `k` of a search without the planner
+pub const BM25_LIMIT_MAX: i32 = 65535;
+pub static BM25_MIN_SCORE: GucSetting<f64> = GucSetting::<f64>::new(0.0);
 pub static ENABLE_INDEX: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static SEGMENT_GROWING_MAX_PAGE_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1000);
 
@@ -11,7 +14,17 @@ pub unsafe fn init() {
         "The maximum number of documents to return in a search",
         &BM25_LIMIT,
         -1,
-        65535,
+        BM25_LIMIT_MAX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_float_guc(
+        "bm25_catalog.bm25_min_score",
+        "bm25 query minimum score",
+        "Only documents with a score greater than this value are returned in a search",
+        &BM25_MIN_SCORE,
+        0.0,
+        f32::MAX as f64,
         GucContext::Userset,
         GucFlags::default(),
     );
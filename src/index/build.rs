@@ -1,14 +1,14 @@
-use pgrx::{itemptr::item_pointer_to_u64, FromDatum, PgMemoryContexts};
+use pgrx::{itemptr::item_pointer_to_u64, PgMemoryContexts};
 
+use super::options::{index_options, options_tokenizer};
 use crate::{
-    datatype::Bm25VectorInput,
     page::{
         page_alloc, page_alloc_init_forknum, page_write, PageFlags, VirtualPageWriter,
         METAPAGE_BLKNO,
     },
     segment::{
         builder::IndexBuilder,
-        meta::{MetaPageData, META_VERSION},
+        meta::{MetaPageData, TokenizerTag, META_MAGIC, META_VERSION},
         sealed::SealedSegmentData,
     },
 };
@@ -25,6 +25,7 @@ pub unsafe extern "C" fn ambuildempty(index: pgrx::pg_sys::Relation) {
     let ptr = meta_page.content.as_mut_ptr() as *mut MetaPageData;
     unsafe {
         ptr.write(MetaPageData {
+            magic: META_MAGIC,
             version: META_VERSION,
             doc_cnt: 0,
             doc_term_cnt: 0,
@@ -40,15 +41,25 @@ pub unsafe extern "C" fn ambuildempty(index: pgrx::pg_sys::Relation) {
                 term_info_blkno: pgrx::pg_sys::InvalidBlockNumber,
                 term_id_cnt: 0,
             },
+            tokenizer: tokenizer_tag(index),
         });
         meta_page.header.pd_lower += std::mem::size_of::<MetaPageData>() as u16;
     }
 }
 
+// the index is bound to the tokenizer in its options
+fn tokenizer_tag(index: pgrx::pg_sys::Relation) -> TokenizerTag {
+    match index_options(index).tokenizer {
+        Some(name) => TokenizerTag::new(&name, crate::token::tokenizer_version(&name)),
+        None => TokenizerTag::NONE,
+    }
+}
+
 struct BuildState {
     heap_tuples: usize,
     index_tuples: usize,
     index: pgrx::pg_sys::Relation,
+    tokenizer: Option<String>,
     builder: IndexBuilder,
     memctx: PgMemoryContexts,
 }
@@ -68,6 +79,7 @@ pub unsafe extern "C" fn ambuild(
         heap_tuples: 0,
         index_tuples: 0,
         index,
+        tokenizer: super::is_text_index(index).then(|| options_tokenizer(index)),
         builder: IndexBuilder::new(),
         memctx: PgMemoryContexts::new("vchord_bm25_index_build"),
     };
@@ -95,7 +107,7 @@ unsafe extern "C" fn build_callback(
     let state = &mut *(state.cast::<BuildState>());
     state.memctx.reset();
     state.memctx.switch_to(|_| {
-        let Some(vector) = Bm25VectorInput::from_datum(*datum, *is_null) else {
+        let Some(vector) = super::index_vector(state.tokenizer.as_deref(), *datum, *is_null) else {
             return;
         };
         let id = item_pointer_to_u64(unsafe { ctid.read() });
@@ -115,6 +127,7 @@ unsafe fn write_down(state: &BuildState) {
     let ptr = meta_page.content.as_mut_ptr() as *mut MetaPageData;
     unsafe {
         ptr.write(MetaPageData {
+            magic: META_MAGIC,
             version: META_VERSION,
             doc_cnt,
             doc_term_cnt,
@@ -130,6 +143,7 @@ unsafe fn write_down(state: &BuildState) {
                 term_info_blkno: pgrx::pg_sys::InvalidBlockNumber,
                 term_id_cnt,
             },
+            tokenizer: tokenizer_tag(state.index),
         });
         meta_page.header.pd_lower += std::mem::size_of::<MetaPageData>() as u16;
     }
@@ -0,0 +1,117 @@
+use pgrx::{
+    itemptr::{item_pointer_to_u64, u64_to_item_pointer},
+    iter::TableIterator,
+    name,
+    pg_sys::ItemPointerData,
+};
+
+use crate::utils::fusion::{normalize, reciprocal_rank_fusion, weighted_fusion, Normalization};
+
+use super::scan::search_top_k;
+
+fn parse_normalization(method: &str) -> Normalization {
+    Normalization::parse(method).unwrap_or_else(|| {
+        pgrx::error!(
+            "unknown normalization method \"{}\", expected \"minmax\" or \"zscore\"",
+            method
+        )
+    })
+}
+
+fn to_rows(
+    results: Vec<(u64, f32)>,
+) -> TableIterator<'static, (name!(ctid, ItemPointerData), name!(score, f32))> {
+    let rows = results
+        .into_iter()
+        .map(|(ctid, score)| {
+            let mut tid = ItemPointerData::default();
+            u64_to_item_pointer(ctid, &mut tid);
+            (tid, score)
+        })
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+// A NULL score stays NULL, the others are normalized without it.
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn bm25_normalize(
+    scores: Vec<Option<f32>>,
+    method: pgrx::default!(&str, "'minmax'"),
+) -> Vec<Option<f32>> {
+    let method = parse_normalization(method);
+    let mut values = scores.iter().flatten().copied().collect::<Vec<_>>();
+    normalize(&mut values, method);
+    let mut values = values.into_iter();
+    scores
+        .into_iter()
+        .map(|score| score.and_then(|_| values.next()))
+        .collect()
+}
+
+// Fuse the bm25 top-k with another ranked list of ctids by reciprocal rank fusion. A NULL ctid is
+// skipped, the documents after it are ranked as if it's not in the list.
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_rrf(
+    index: pgrx::PgRelation,
+    query: pgrx::composite_type!("bm25query"),
+    k: i32,
+    other: Vec<Option<ItemPointerData>>,
+    rrf_k: pgrx::default!(i32, 60),
+) -> TableIterator<'static, (name!(ctid, ItemPointerData), name!(score, f32))> {
+    if rrf_k < 0 {
+        pgrx::error!("rrf_k must be non-negative, got {}", rrf_k);
+    }
+    let bm25 = search_top_k(&index, &query, k)
+        .into_iter()
+        .map(|(_, _, ctid)| ctid)
+        .collect::<Vec<_>>();
+    let other = other
+        .into_iter()
+        .flatten()
+        .map(item_pointer_to_u64)
+        .collect::<Vec<_>>();
+    to_rows(reciprocal_rank_fusion(&[&bm25, &other], rrf_k as f32))
+}
+
+// Fuse the bm25 top-k with another list of scored ctids, by a weighted sum of normalized scores.
+// A pair with a NULL ctid or a NULL score is skipped.
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_weighted_fusion(
+    index: pgrx::PgRelation,
+    query: pgrx::composite_type!("bm25query"),
+    k: i32,
+    other: Vec<Option<ItemPointerData>>,
+    other_scores: Vec<Option<f32>>,
+    bm25_weight: pgrx::default!(f32, 0.5),
+    normalization: pgrx::default!(&str, "'minmax'"),
+) -> TableIterator<'static, (name!(ctid, ItemPointerData), name!(score, f32))> {
+    let method = parse_normalization(normalization);
+    if !(0.0..=1.0).contains(&bm25_weight) {
+        pgrx::error!("bm25_weight must be between 0 and 1, got {}", bm25_weight);
+    }
+    if other.len() != other_scores.len() {
+        pgrx::error!(
+            "other has {} ctids, but other_scores has {} scores",
+            other.len(),
+            other_scores.len()
+        );
+    }
+    let (mut bm25_scores, bm25): (Vec<_>, Vec<_>) = search_top_k(&index, &query, k)
+        .into_iter()
+        .map(|(score, _, ctid)| (score, ctid))
+        .unzip();
+    let (other, mut other_scores): (Vec<_>, Vec<_>) = other
+        .into_iter()
+        .zip(other_scores)
+        .filter_map(|pair| match pair {
+            (Some(ctid), Some(score)) => Some((item_pointer_to_u64(ctid), score)),
+            _ => None,
+        })
+        .unzip();
+    normalize(&mut bm25_scores, method);
+    normalize(&mut other_scores, method);
+    to_rows(weighted_fusion(&[
+        (&bm25, &bm25_scores, bm25_weight),
+        (&other, &other_scores, 1.0 - bm25_weight),
+    ]))
+}
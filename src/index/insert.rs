@@ -1,14 +1,13 @@
 use lending_iterator::LendingIterator;
-use pgrx::{itemptr::item_pointer_to_u64, FromDatum};
+use pgrx::itemptr::item_pointer_to_u64;
 
 use crate::{
-    datatype::Bm25VectorInput,
-    page::{page_free, page_read, page_write, VirtualPageWriter, METAPAGE_BLKNO},
+    page::{page_free, page_read, VirtualPageWriter, METAPAGE_BLKNO},
     segment::{
         delete::extend_delete_bit,
         field_norm::fieldnorm_to_id,
         growing::{GrowingSegmentData, GrowingSegmentReader},
-        meta::MetaPageData,
+        meta::{metapage_write, MetaPageData},
         posting::{InvertedAppender, InvertedWriter},
         sealed::extend_sealed_term_id,
         term_stat::{extend_term_id, TermStatReader},
@@ -27,14 +26,19 @@ pub unsafe extern "C" fn aminsert(
     _index_unchanged: bool,
     _index_info: *mut pgrx::pg_sys::IndexInfo,
 ) -> bool {
-    let Some(vector) = Bm25VectorInput::from_datum(*values, *is_null) else {
+    // the text is tokenized before the metapage is locked, and never grows the vocabulary
+    let tokenizer = super::is_text_index(index).then(|| {
+        super::check_tokenizer_version(index, &super::index_tokenizer_tag(index));
+        super::index_tokenizer(index)
+    });
+    let Some(vector) = super::index_vector(tokenizer.as_deref(), *values, *is_null) else {
         return false;
     };
 
     let vector_borrow = vector.borrow();
     let doc_len = vector_borrow.doc_len();
 
-    let mut metapage = page_write(index, METAPAGE_BLKNO);
+    let mut metapage = metapage_write(index);
 
     let meta: &mut MetaPageData = metapage.as_mut();
     let current_doc_id = meta.current_doc_id;
@@ -106,7 +110,7 @@ pub unsafe extern "C" fn aminsert(
         writer.finalize();
         let term_id_cnt = writer.term_id_cnt();
 
-        let mut metapage = page_write(index, METAPAGE_BLKNO);
+        let mut metapage = metapage_write(index);
         let meta: &mut MetaPageData = metapage.as_mut();
         extend_sealed_term_id(index, &mut meta.sealed_segment, term_id_cnt);
         let mut appender = InvertedAppender::new(index, meta);
@@ -1,10 +1,115 @@
 mod am;
 mod build;
+mod fusion;
 mod insert;
 mod options;
 mod scan;
+mod stats;
+mod upgrade;
 mod vacuum;
 
+use pgrx::FromDatum;
+
+use crate::{
+    datatype::Bm25VectorInput,
+    segment::meta::{MetaPageData, TokenizerTag},
+};
+
+pub use options::{index_tokenizer, index_tokenizer_tag};
+pub use stats::Bm25Stats;
+
 pub fn init() {
     options::init();
+    stats::init();
+}
+
+fn is_bm25(index: &pgrx::PgRelation) -> bool {
+    let am_oid = unsafe { pgrx::pg_sys::get_am_oid(c"bm25".as_ptr(), false) };
+    unsafe { (*index.rd_rel).relam == am_oid }
+}
+
+pub fn check_bm25_index(index: &pgrx::PgRelation) {
+    if !index.is_index() || !is_bm25(index) {
+        pgrx::error!("\"{}\" is not a bm25 index", index.name());
+    }
+}
+
+// a partitioned bm25 index is accepted as well, it has no storage
+pub fn check_bm25_or_partitioned_index(index: &pgrx::PgRelation) {
+    if !(index.is_index() || stats::is_partitioned_index(index.oid())) || !is_bm25(index) {
+        pgrx::error!("\"{}\" is not a bm25 index", index.name());
+    }
+}
+
+// whether the index is on a text column, see `text_bm25_ops`
+fn is_text_index(index: pgrx::pg_sys::Relation) -> bool {
+    unsafe { *(*index).rd_opcintype == pgrx::pg_sys::TEXTOID }
+}
+
+// A query tokenized by another tokenizer than the one the index is built with is rejected, since
+// the term ids of different tokenizers are unrelated. So is a query without a tokenizer, such as
+// one built from a vector, since it can't be checked. The version of the tokenizer is always
+// checked, whether the query has a tokenizer or not.
+pub fn check_query_tokenizer(
+    index: pgrx::pg_sys::Relation,
+    meta: &MetaPageData,
+    tokenizer: Option<&str>,
+) {
+    if let Some(expected) = meta.tokenizer.name() {
+        match tokenizer {
+            Some(tokenizer) if tokenizer == expected => {}
+            Some(tokenizer) => {
+                let index = unsafe { pgrx::PgRelation::from_pg(index) };
+                pgrx::error!(
+                    "the query is tokenized by \"{}\", but bm25 index \"{}\" is built with tokenizer \"{}\"",
+                    tokenizer,
+                    index.name(),
+                    expected
+                );
+            }
+            None => {
+                let index = unsafe { pgrx::PgRelation::from_pg(index) };
+                pgrx::error!(
+                    "the query has no tokenizer, but bm25 index \"{}\" is built with tokenizer \"{}\", build it by `to_bm25query` with the text",
+                    index.name(),
+                    expected
+                );
+            }
+        }
+    }
+    check_tokenizer_version(index, &meta.tokenizer);
+}
+
+// The terms ids of another version of the tokenizer may stand for other tokens, so the index is
+// rebuilt when the version of its tokenizer changes.
+pub fn check_tokenizer_version(index: pgrx::pg_sys::Relation, tag: &TokenizerTag) {
+    let Some(tokenizer) = tag.name() else {
+        return;
+    };
+    let version = crate::token::tokenizer_version(tokenizer);
+    if version != tag.version {
+        let index = unsafe { pgrx::PgRelation::from_pg(index) };
+        pgrx::error!(
+            "bm25 index \"{}\" is built with version {} of tokenizer \"{}\", but it's at version {} now, run `REINDEX` to rebuild it",
+            index.name(),
+            tag.version,
+            tokenizer,
+            version
+        );
+    }
+}
+
+// the vector of an indexed value, a text value is tokenized by `tokenizer`
+pub unsafe fn index_vector<'a>(
+    tokenizer: Option<&str>,
+    datum: pgrx::pg_sys::Datum,
+    is_null: bool,
+) -> Option<Bm25VectorInput<'a>> {
+    let Some(tokenizer) = tokenizer else {
+        return unsafe { Bm25VectorInput::from_datum(datum, is_null) };
+    };
+    let text = unsafe { <&str>::from_datum(datum, is_null) }?;
+    Some(Bm25VectorInput::Owned(crate::token::tokenize(
+        text, tokenizer,
+    )))
 }
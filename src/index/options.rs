@@ -1,6 +1,10 @@
+use crate::segment::meta::{metapage_read, MetaPageData, TokenizerTag};
 use crate::utils::cells::PgCell;
+use serde::Deserialize;
 use std::ffi::CStr;
 
+use super::{check_bm25_or_partitioned_index, stats::is_partitioned_index};
+
 static RELOPT_KIND_BM25: PgCell<pgrx::pg_sys::relopt_kind::Type> = unsafe { PgCell::new(0) };
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -17,7 +21,6 @@ impl Reloption {
         offset: std::mem::offset_of!(Reloption, options) as i32,
     }];
 
-    #[allow(unused)]
     pub unsafe fn options(&self) -> &CStr {
         unsafe {
             let ptr = std::ptr::addr_of!(*self)
@@ -28,6 +31,77 @@ impl Reloption {
     }
 }
 
+// the content of the `options` reloption
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IndexOptions {
+    // the tokenizer of an index on a text column, see `text_bm25_ops`
+    #[serde(default)]
+    pub tokenizer: Option<String>,
+}
+
+impl IndexOptions {
+    fn parse(options: &CStr) -> Self {
+        let options = options.to_str().expect("options is not valid UTF-8");
+        toml::from_str(options)
+            .unwrap_or_else(|e| pgrx::error!("Invalid index options, Details: {}", e))
+    }
+}
+
+pub fn index_options(index: pgrx::pg_sys::Relation) -> IndexOptions {
+    let rd_options = unsafe { (*index).rd_options } as *const Reloption;
+    if rd_options.is_null() {
+        return IndexOptions::default();
+    }
+    unsafe { IndexOptions::parse((*rd_options).options()) }
+}
+
+// The tokenizer the index is built with, or the one in its options if it's not recorded. A
+// partitioned index has no storage, its partitions are created with the same options.
+pub fn index_tokenizer(index: pgrx::pg_sys::Relation) -> String {
+    if is_partitioned_index(unsafe { (*index).rd_id }) {
+        return options_tokenizer(index);
+    }
+    match index_tokenizer_tag(index).name() {
+        Some(name) => name.to_string(),
+        None => options_tokenizer(index),
+    }
+}
+
+// The tokenizer recorded in the metapage, cached in `rd_amcache` so that it's not read for every
+// insert. It's only written when the index is built, which resets the relcache entry and the cache.
+pub fn index_tokenizer_tag(index: pgrx::pg_sys::Relation) -> TokenizerTag {
+    unsafe {
+        if (*index).rd_amcache.is_null() {
+            let page = metapage_read(index);
+            let meta: &MetaPageData = page.as_ref();
+            let cache =
+                pgrx::pg_sys::MemoryContextAlloc((*index).rd_indexcxt, size_of::<TokenizerTag>())
+                    .cast::<TokenizerTag>();
+            cache.write(meta.tokenizer);
+            (*index).rd_amcache = cache.cast();
+        }
+        (*index).rd_amcache.cast::<TokenizerTag>().read()
+    }
+}
+
+// the tokenizer in the index options
+pub fn options_tokenizer(index: pgrx::pg_sys::Relation) -> String {
+    index_options(index).tokenizer.unwrap_or_else(|| {
+        let index = unsafe { pgrx::PgRelation::from_pg(index) };
+        pgrx::error!(
+            "bm25 index \"{}\" has no tokenizer in its options",
+            index.name()
+        )
+    })
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe)]
+pub fn bm25_index_tokenizer(index: pgrx::PgRelation) -> String {
+    check_bm25_or_partitioned_index(&index);
+    index_tokenizer(index.as_ptr())
+}
+
 #[pgrx::pg_guard]
 pub unsafe extern "C" fn amoptions(
     reloptions: pgrx::pg_sys::Datum,
@@ -43,6 +117,9 @@ pub unsafe extern "C" fn amoptions(
             Reloption::TAB.len() as _,
         )
     };
+    if validate && !rdopts.is_null() {
+        unsafe { IndexOptions::parse((*rdopts.cast::<Reloption>()).options()) };
+    }
     rdopts as *mut pgrx::pg_sys::bytea
 }
 
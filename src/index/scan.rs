@@ -1,31 +1,95 @@
 use std::num::NonZero;
 
 use lending_iterator::LendingIterator;
-use pgrx::{prelude::PgHeapTuple, FromDatum};
+use pgrx::{
+    heap_tuple::PgHeapTuple,
+    itemptr::{item_pointer_to_u64, u64_to_item_pointer},
+    iter::TableIterator,
+    name, AllocatedByRust, FromDatum,
+};
 
 use crate::{
     algorithm::block_wand::{block_wand, block_wand_single, SealedScorer},
     datatype::{Bm25VectorBorrowed, Bm25VectorOutput},
-    guc::BM25_LIMIT,
-    page::{page_read, METAPAGE_BLKNO},
+    guc::{BM25_LIMIT, BM25_LIMIT_MAX, BM25_MIN_SCORE},
+    index::{check_bm25_index, check_query_tokenizer},
     segment::{
-        delete::DeleteBitmapReader, field_norm::FieldNormReader, growing::GrowingSegmentReader,
-        meta::MetaPageData, payload::PayloadReader, sealed::SealedSegmentReader,
-        term_stat::TermStatReader,
+        delete::DeleteBitmapReader,
+        field_norm::FieldNormReader,
+        growing::GrowingSegmentReader,
+        meta::{metapage_read, MetaPageData},
+        payload::PayloadReader,
+        sealed::SealedSegmentReader,
+        term_stat::TermStatRead,
+    },
+    utils::{
+        collector::{AfterCollector, Collector, RankedResults, ThresholdCollector},
+        loser_tree::LoserTree,
+        topk_computer::TopKComputer,
     },
-    utils::{loser_tree::LoserTree, topk_computer::TopKComputer},
     weight::{bm25_score_batch, idf, Bm25Weight},
 };
 
+use super::stats::{is_partitioned_index, Bm25Stats, PartitionedStats};
+
+// the last document of the previous page, see `bm25query_search_after`
+#[derive(Clone, Copy)]
+struct Cursor {
+    score: f32,
+    ctid: u64,
+    // the table of the document, if it's given the ties are ordered by table and ctid
+    table: Option<pgrx::pg_sys::Oid>,
+}
+
+// the fields of a `bm25query`
+struct Query {
+    index_oid: pgrx::pg_sys::Oid,
+    vector: Bm25VectorOutput,
+    tokenizer: Option<String>,
+    cursor: Option<Cursor>,
+}
+
 enum Scanner {
     Initial,
     Waiting {
-        query_index: pgrx::PgRelation,
-        query_vector: Bm25VectorOutput,
+        query: Query,
+        partitioned_stats: Option<PartitionedStats>,
     },
     Scanned {
         results: Vec<u64>,
     },
+    // `bm25_limit` is -1, the ctids are read as the documents are fetched
+    Streaming {
+        results: RankedResults,
+        payload_reader: PayloadReader,
+    },
+}
+
+fn parse_bm25query(bm25_query: &PgHeapTuple<'_, AllocatedByRust>) -> Query {
+    let index_oid = bm25_query
+        .get_by_index(NonZero::new(1).unwrap())
+        .unwrap()
+        .unwrap();
+    let vector = bm25_query
+        .get_by_index(NonZero::new(2).unwrap())
+        .unwrap()
+        .unwrap();
+    let after_rank: Option<f32> = bm25_query.get_by_index(NonZero::new(3).unwrap()).unwrap();
+    let after_ctid: Option<pgrx::pg_sys::ItemPointerData> =
+        bm25_query.get_by_index(NonZero::new(4).unwrap()).unwrap();
+    let after_table = bm25_query.get_by_index(NonZero::new(6).unwrap()).unwrap();
+    let cursor = after_rank.zip(after_ctid).map(|(rank, ctid)| Cursor {
+        score: -rank,
+        ctid: item_pointer_to_u64(ctid),
+        table: after_table,
+    });
+    let tokenizer = bm25_query.get_by_index(NonZero::new(5).unwrap()).unwrap();
+    Query {
+        index_oid,
+        vector,
+        tokenizer,
+        cursor,
+    }
 }
 
 #[pgrx::pg_guard]
@@ -59,19 +123,13 @@ pub unsafe extern "C" fn amrescan(
     let value = (*data).sk_argument;
     let is_null = ((*data).sk_flags & pgrx::pg_sys::SK_ISNULL as i32) != 0;
     let bm25_query = PgHeapTuple::from_datum(value, is_null).unwrap();
-    let index_oid = bm25_query
-        .get_by_index(NonZero::new(1).unwrap())
-        .unwrap()
-        .unwrap();
-    let query_vector = bm25_query
-        .get_by_index(NonZero::new(2).unwrap())
-        .unwrap()
-        .unwrap();
+    let query = parse_bm25query(&bm25_query);
+    let partitioned_stats = check_query(&query, (*scan).indexRelation);
 
     let scanner = (*scan).opaque.cast::<Scanner>().as_mut().unwrap();
     *scanner = Scanner::Waiting {
-        query_index: pgrx::PgRelation::with_lock(index_oid, pgrx::pg_sys::AccessShareLock as _),
-        query_vector,
+        query,
+        partitioned_stats,
     };
 }
 
@@ -85,24 +143,53 @@ pub unsafe extern "C" fn amgettuple(
     }
 
     let scanner = unsafe { (*scan).opaque.cast::<Scanner>().as_mut().unwrap() };
-    let results = match scanner {
-        Scanner::Initial => return false,
-        Scanner::Waiting {
-            query_index,
-            query_vector,
-        } => {
-            let results = scan_main(query_index.as_ptr(), query_vector.borrow());
-            *scanner = Scanner::Scanned { results };
-            let Scanner::Scanned { results } = scanner else {
-                unreachable!()
+    if let Scanner::Waiting {
+        query,
+        partitioned_stats,
+    } = scanner
+    {
+        let index = (*scan).indexRelation;
+        let limit = BM25_LIMIT.get();
+        let min_score = BM25_MIN_SCORE.get() as f32;
+        *scanner = if limit == -1 {
+            // brute force search returns all documents of the growing segment, even without a score
+            let brute_force = min_score == 0.0;
+            let threshold = if brute_force {
+                f32::NEG_INFINITY
+            } else {
+                min_score
             };
-            results
-        }
-        Scanner::Scanned { results } => results,
+            let mut collector = ThresholdCollector::new(threshold);
+            let payload_reader = scan_main(
+                index,
+                query,
+                partitioned_stats.as_ref(),
+                brute_force,
+                &mut collector,
+            );
+            Scanner::Streaming {
+                results: collector.into_ranked(),
+                payload_reader,
+            }
+        } else {
+            let results = scan_top_k(index, query, partitioned_stats.as_ref(), limit, min_score);
+            Scanner::Scanned {
+                results: results.into_iter().map(|(_, _, ctid)| ctid).collect(),
+            }
+        };
+    }
+
+    let tid = match scanner {
+        Scanner::Initial | Scanner::Waiting { .. } => return false,
+        Scanner::Scanned { results } => results.pop(),
+        Scanner::Streaming {
+            results,
+            payload_reader,
+        } => results.pop().map(|(_, doc_id)| payload_reader.read(doc_id)),
     };
 
-    if let Some(tid) = results.pop() {
-        pgrx::itemptr::u64_to_item_pointer(tid, &mut (*scan).xs_heaptid);
+    if let Some(tid) = tid {
+        u64_to_item_pointer(tid, &mut (*scan).xs_heaptid);
         (*scan).xs_recheckorderby = false;
         (*scan).xs_recheck = false;
         true
@@ -117,33 +204,199 @@ pub unsafe extern "C" fn amendscan(scan: pgrx::pg_sys::IndexScanDesc) {
     *scanner = Scanner::Initial;
 }
 
-// return top-k results
-fn scan_main(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorrowed) -> Vec<u64> {
-    let limit = BM25_LIMIT.get();
+// Run a search in the index without the planner, the hits are returned in descending order of score.
+// The scan honors `bm25_catalog.bm25_min_score` and the cursor of the query, but not `bm25_limit`.
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_search(
+    index: pgrx::PgRelation,
+    query: pgrx::composite_type!("bm25query"),
+    k: i32,
+) -> TableIterator<
+    'static,
+    (
+        name!(ctid, pgrx::pg_sys::ItemPointerData),
+        name!(score, f32),
+        name!(doc_id, i64),
+    ),
+> {
+    let rows = search_top_k(&index, &query, k)
+        .into_iter()
+        .map(|(score, doc_id, ctid)| {
+            let mut tid = pgrx::pg_sys::ItemPointerData::default();
+            u64_to_item_pointer(ctid, &mut tid);
+            (tid, score, doc_id as i64)
+        })
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+// return (score, doc_id, ctid) of the top-k documents in descending order of score
+pub fn search_top_k(
+    index: &pgrx::PgRelation,
+    query: &PgHeapTuple<'_, AllocatedByRust>,
+    k: i32,
+) -> Vec<(f32, u32, u64)> {
+    check_bm25_index(index);
+    if !(0..=BM25_LIMIT_MAX).contains(&k) {
+        pgrx::error!("k must be between 0 and {}, got {}", BM25_LIMIT_MAX, k);
+    }
+    let query = parse_bm25query(query);
+    let partitioned_stats = check_query(&query, index.as_ptr());
+    let mut results = scan_top_k(
+        index.as_ptr(),
+        &query,
+        partitioned_stats.as_ref(),
+        k,
+        BM25_MIN_SCORE.get() as f32,
+    );
+    results.reverse();
+    results
+}
+
+// The postings and the payloads are read from the scanned index, so a query built for another
+// index would return the tids of another table. A query built for a partitioned index can be run
+// on the index of any of its partitions, with the statistics of all of them.
+fn check_query(query: &Query, index: pgrx::pg_sys::Relation) -> Option<PartitionedStats> {
+    let index = unsafe { pgrx::PgRelation::from_pg(index) };
+    if !is_partitioned_index(query.index_oid) {
+        if query.index_oid != index.oid() {
+            pgrx::error!(
+                "the query is built for the index with oid {}, not \"{}\"",
+                query.index_oid.as_u32(),
+                index.name()
+            );
+        }
+        return None;
+    }
+    let stats = PartitionedStats::of_query(
+        query.index_oid,
+        query.tokenizer.as_deref(),
+        query.vector.borrow(),
+    );
+    if !stats.leaf_indexes.contains(&index.oid()) {
+        pgrx::error!(
+            "the query is built for the partitioned index with oid {}, \"{}\" is not one of its partitions",
+            query.index_oid.as_u32(),
+            index.name()
+        );
+    }
+    // ctids are only unique in a table, so the ties of the partitions are told apart by table
+    if query.cursor.is_some_and(|cursor| cursor.table.is_none()) {
+        pgrx::error!(
+            "the cursor of a query built for the partitioned index with oid {} needs the tableoid of the document, see `bm25query_search_after`",
+            query.index_oid.as_u32()
+        );
+    }
+    Some(stats)
+}
+
+// return (score, doc_id, ctid) of the top `limit` documents, from the lowest ranked to the highest
+fn scan_top_k(
+    index: pgrx::pg_sys::Relation,
+    query: &Query,
+    partitioned_stats: Option<&PartitionedStats>,
+    limit: i32,
+    min_score: f32,
+) -> Vec<(f32, u32, u64)> {
     if limit == 0 {
+        // nothing is kept, but the query is still checked against the index
+        let mut collector = ThresholdCollector::new(f32::INFINITY);
+        scan_main(index, query, partitioned_stats, false, &mut collector);
         return Vec::new();
     }
-    if limit == -1 {
-        return brute_force_scan(index, query_vector);
-    }
+    let mut computer = TopKComputer::with_threshold(limit as _, min_score);
+    let payload_reader = scan_main(index, query, partitioned_stats, false, &mut computer);
+    computer
+        .to_sorted_slice()
+        .iter()
+        .map(|&(score, doc_id)| (score, doc_id, payload_reader.read(doc_id)))
+        .collect()
+}
 
-    let page = page_read(index, METAPAGE_BLKNO);
+// push the documents of the index into `collector`, the returned reader maps them to ctids
+fn scan_main(
+    index: pgrx::pg_sys::Relation,
+    query: &Query,
+    partitioned_stats: Option<&PartitionedStats>,
+    brute_force: bool,
+    collector: &mut impl Collector,
+) -> PayloadReader {
+    let page = metapage_read(index);
     let meta: &MetaPageData = page.as_ref();
-    let avgdl = meta.avgdl();
+    check_query_tokenizer(index, meta, query.tokenizer.as_deref());
+    let stats = match partitioned_stats {
+        Some(stats) => stats.to_stats(),
+        None => Bm25Stats::new(index, meta),
+    };
+    search_after(
+        index,
+        meta,
+        &stats,
+        query.vector.borrow(),
+        query.cursor,
+        brute_force,
+        collector,
+    );
+    PayloadReader::new(index, meta.payload_blkno)
+}
 
-    let mut computer = TopKComputer::new(BM25_LIMIT.get() as _);
+fn search_after(
+    index: pgrx::pg_sys::Relation,
+    meta: &MetaPageData,
+    stats: &Bm25Stats,
+    query_vector: Bm25VectorBorrowed,
+    cursor: Option<Cursor>,
+    brute_force: bool,
+    collector: &mut impl Collector,
+) {
+    let Some(cursor) = cursor else {
+        return search(index, meta, stats, query_vector, brute_force, collector);
+    };
+    let mut after = AfterCollector::new(collector, cursor.score);
+    search(index, meta, stats, query_vector, brute_force, &mut after);
+    let payload_reader = PayloadReader::new(index, meta.payload_blkno);
+    match cursor.table {
+        // the ties are ordered by table, and then by ctid
+        Some(table) => {
+            let heap = unsafe { (*(*index).rd_index).indrelid };
+            let cursor_key = (table.as_u32(), cursor.ctid);
+            after.finish(|doc_id| (heap.as_u32(), payload_reader.read(doc_id)) > cursor_key);
+        }
+        // The ties are ordered by doc id. The cursor is the newest one with its ctid, older ones
+        // are deleted before the ctid is reused. If it's deleted, all ties are kept.
+        None => {
+            let cursor_id = after
+                .ties()
+                .iter()
+                .copied()
+                .filter(|&doc_id| payload_reader.read(doc_id) == cursor.ctid)
+                .max();
+            after.finish(|doc_id| cursor_id.is_none_or(|cursor_id| doc_id > cursor_id));
+        }
+    }
+}
+
+// Scores are summed in the order of term id, as `bm25_score_batch` does, so that the index
+// returns exactly the scores of the `<&>` operator.
+fn search(
+    index: pgrx::pg_sys::Relation,
+    meta: &MetaPageData,
+    stats: &Bm25Stats,
+    query_vector: Bm25VectorBorrowed,
+    brute_force: bool,
+    collector: &mut impl Collector,
+) {
+    let avgdl = stats.avgdl();
     let delete_bitmap_reader = DeleteBitmapReader::new(index, meta.delete_bitmap_blkno);
 
-    let term_stat_reader = TermStatReader::new(index, meta);
     if let Some(growing) = meta.growing_segment.as_ref() {
         let reader = GrowingSegmentReader::new(index, growing);
         let mut doc_id = meta.sealed_doc_id;
         let mut iter = reader.into_lending_iter();
         while let Some(vector) = iter.next() {
             if !delete_bitmap_reader.is_delete(doc_id) {
-                let score =
-                    bm25_score_batch(meta.doc_cnt, avgdl, &term_stat_reader, vector, query_vector);
-                computer.push(score, doc_id);
+                let score = bm25_score_batch(stats.doc_cnt, avgdl, stats, vector, query_vector);
+                collector.push(score, doc_id);
             }
             doc_id += 1;
         }
@@ -157,10 +410,11 @@ fn scan_main(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorrowed) ->
         .zip(query_vector.values())
         .filter_map(|(&term_id, &term_tf)| {
             sealed_reader.get_postings(term_id).map(|posting_reader| {
-                let term_cnt = term_stat_reader.read(term_id);
-                let idf = idf(meta.doc_cnt, term_cnt);
+                let term_cnt = stats.read(term_id);
+                let idf = idf(stats.doc_cnt, term_cnt);
                 let weight = Bm25Weight::new(term_tf, idf, avgdl);
                 SealedScorer {
+                    term_id,
                     posting: posting_reader,
                     weight,
                     max_score: weight.max_score(),
@@ -169,61 +423,31 @@ fn scan_main(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorrowed) ->
         })
         .collect::<Vec<_>>();
 
-    if scorers.len() == 1 {
+    if brute_force {
+        brute_force_scan(scorers, &fieldnorm_reader, &delete_bitmap_reader, collector);
+    } else if scorers.len() == 1 {
         block_wand_single(
             scorers.into_iter().next().unwrap(),
             &fieldnorm_reader,
             &delete_bitmap_reader,
-            &mut computer,
+            collector,
         );
     } else {
-        block_wand(
-            scorers,
-            &fieldnorm_reader,
-            &delete_bitmap_reader,
-            &mut computer,
-        );
+        block_wand(scorers, &fieldnorm_reader, &delete_bitmap_reader, collector);
     }
-
-    let payload_reader = PayloadReader::new(index, meta.payload_blkno);
-    computer
-        .to_sorted_slice()
-        .iter()
-        .map(|(_, doc_id)| payload_reader.read(*doc_id))
-        .collect()
 }
 
-fn brute_force_scan(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorrowed) -> Vec<u64> {
-    let mut results = Vec::new();
-
-    let page = page_read(index, METAPAGE_BLKNO);
-    let meta: &MetaPageData = page.as_ref();
-    let avgdl = meta.avgdl();
-
-    let delete_bitmap_reader = DeleteBitmapReader::new(index, meta.delete_bitmap_blkno);
-
-    let term_stat_reader = TermStatReader::new(index, meta);
-    if let Some(growing) = meta.growing_segment.as_ref() {
-        let reader = GrowingSegmentReader::new(index, growing);
-        let mut doc_id = meta.sealed_doc_id;
-        let mut iter = reader.into_lending_iter();
-        while let Some(vector) = iter.next() {
-            if !delete_bitmap_reader.is_delete(doc_id) {
-                let score =
-                    bm25_score_batch(meta.doc_cnt, avgdl, &term_stat_reader, vector, query_vector);
-                results.push((score, doc_id));
-            }
-            doc_id += 1;
-        }
-    }
-
-    let fieldnorm_reader = FieldNormReader::new(index, meta.field_norm_blkno);
-    let sealed_reader = SealedSegmentReader::new(index, meta.sealed_segment);
-
-    struct Cmp(f32, u32);
+fn brute_force_scan(
+    scorers: Vec<SealedScorer>,
+    fieldnorm_reader: &FieldNormReader,
+    delete_bitmap_reader: &DeleteBitmapReader,
+    collector: &mut impl Collector,
+) {
+    // ordered by document, and then by term
+    struct Cmp(f32, u32, u32);
     impl PartialEq for Cmp {
         fn eq(&self, other: &Self) -> bool {
-            self.1.eq(&other.1)
+            (self.1, self.2).eq(&(other.1, other.2))
         }
     }
     impl Eq for Cmp {}
@@ -234,37 +458,27 @@ fn brute_force_scan(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorro
     }
     impl Ord for Cmp {
         fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            self.1.cmp(&other.1)
+            (self.1, self.2).cmp(&(other.1, other.2))
         }
     }
 
-    let iters = query_vector
-        .indexes()
-        .iter()
-        .zip(query_vector.values())
-        .filter_map(|(&term_id, &term_tf)| {
-            sealed_reader.get_postings(term_id).map(|posting_reader| {
-                let term_cnt = term_stat_reader.read(term_id);
-                let idf = idf(meta.doc_cnt, term_cnt);
-                let weight = Bm25Weight::new(term_tf, idf, avgdl);
-                SealedScorer {
-                    posting: posting_reader,
-                    weight,
-                    max_score: weight.max_score(),
-                }
-                .into_iter(&fieldnorm_reader, &delete_bitmap_reader)
-                .map(|(a, b)| Cmp(a, b))
-            })
+    let iters = scorers
+        .into_iter()
+        .map(|scorer| {
+            let term_id = scorer.term_id;
+            scorer
+                .into_iter(fieldnorm_reader, delete_bitmap_reader)
+                .map(move |(a, b)| Cmp(a, b, term_id))
         })
         .collect::<Vec<_>>();
     let loser_tree = LoserTree::new(iters);
 
     let mut cur_docid = None;
     let mut cur_score = 0.;
-    for Cmp(score, docid) in loser_tree {
+    for Cmp(score, docid, _) in loser_tree {
         if Some(docid) != cur_docid {
             if let Some(docid) = cur_docid {
-                results.push((cur_score, docid));
+                collector.push(cur_score, docid);
             }
             cur_docid = Some(docid);
             cur_score = 0.;
@@ -272,13 +486,6 @@ fn brute_force_scan(index: pgrx::pg_sys::Relation, query_vector: Bm25VectorBorro
         cur_score += score;
     }
     if let Some(docid) = cur_docid {
-        results.push((cur_score, docid));
+        collector.push(cur_score, docid);
     }
-
-    results.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
-    let payload_reader = PayloadReader::new(index, meta.payload_blkno);
-    results
-        .into_iter()
-        .map(|(_, doc_id)| payload_reader.read(doc_id))
-        .collect()
 }
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+
+use pgrx::list::List;
+
+use crate::{
+    datatype::Bm25VectorBorrowed,
+    segment::{
+        meta::{metapage_read, MetaPageData},
+        term_stat::{TermStatRead, TermStatReader},
+    },
+    utils::cells::PgRefCell,
+};
+
+use super::check_query_tokenizer;
+
+pub fn init() {
+    unsafe {
+        pgrx::pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+        pgrx::pg_sys::RegisterSubXactCallback(Some(subxact_callback), std::ptr::null_mut());
+    }
+}
+
+pub fn is_partitioned_index(index_oid: pgrx::pg_sys::Oid) -> bool {
+    let relkind = unsafe { pgrx::pg_sys::get_rel_relkind(index_oid) };
+    relkind == pgrx::pg_sys::RELKIND_PARTITIONED_INDEX as std::ffi::c_char
+}
+
+// `catalog/pg_inherits.h` is not in the bindings of pgrx, so the call is guarded by
+// `pg_guard_ffi_boundary`
+extern "C" {
+    fn find_all_inheritors(
+        parent: pgrx::pg_sys::Oid,
+        lockmode: pgrx::pg_sys::LOCKMODE,
+        numparents: *mut *mut pgrx::pg_sys::List,
+    ) -> *mut pgrx::pg_sys::List;
+}
+
+// The indexes of the leaf partitions of a partitioned index, at any level. They're read from
+// `pg_inherits`, since it's called in the index scans and the `<&>` operator, where SPI is not
+// welcome. They're not locked here, but when their metapages are read.
+fn leaf_indexes(index_oid: pgrx::pg_sys::Oid) -> Vec<pgrx::pg_sys::Oid> {
+    let list = unsafe {
+        pgrx::pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            find_all_inheritors(index_oid, pgrx::pg_sys::NoLock as _, std::ptr::null_mut())
+        })
+    };
+    pgrx::memcx::current_context(|cx| {
+        let list = unsafe { List::<pgrx::pg_sys::Oid>::downcast_ptr_in_memcx(list, cx) }
+            .expect("the inheritors are not a list of oids");
+        list.iter()
+            .copied()
+            .filter(|&oid| {
+                let relkind = unsafe { pgrx::pg_sys::get_rel_relkind(oid) };
+                relkind == pgrx::pg_sys::RELKIND_INDEX as std::ffi::c_char
+            })
+            .collect()
+    })
+}
+
+// The statistics of the documents the weight of a term is computed from. A query built for a
+// partitioned index takes them from all of its partitions, so that a document gets the same
+// score in whichever partition it's stored, and the top-k of the partitions can be merged. They
+// are read when the query is run, so they follow the partitions attached, detached or changed.
+pub struct Bm25Stats {
+    pub doc_cnt: u32,
+    doc_term_cnt: u64,
+    term_cnts: TermCnts,
+}
+
+enum TermCnts {
+    // the term statistics of the index, the index stays open while they are read
+    Index(TermStatReader, Option<pgrx::PgRelation>),
+    // the document count of each term of the query, in all partitions
+    Query(BTreeMap<u32, u32>),
+}
+
+impl Bm25Stats {
+    pub fn new(index: pgrx::pg_sys::Relation, meta: &MetaPageData) -> Self {
+        Self {
+            doc_cnt: meta.doc_cnt,
+            doc_term_cnt: meta.doc_term_cnt,
+            term_cnts: TermCnts::Index(TermStatReader::new(index, meta), None),
+        }
+    }
+
+    // the statistics of the index the query is built for
+    pub fn of_query(
+        index_oid: pgrx::pg_sys::Oid,
+        tokenizer: Option<&str>,
+        query_vector: Bm25VectorBorrowed,
+    ) -> Self {
+        if is_partitioned_index(index_oid) {
+            return PartitionedStats::of_query(index_oid, tokenizer, query_vector).to_stats();
+        }
+        let index =
+            unsafe { pgrx::PgRelation::with_lock(index_oid, pgrx::pg_sys::AccessShareLock as _) };
+        let page = metapage_read(index.as_ptr());
+        let meta: &MetaPageData = page.as_ref();
+        check_query_tokenizer(index.as_ptr(), meta, tokenizer);
+        let mut stats = Self::new(index.as_ptr(), meta);
+        drop(page);
+        if let TermCnts::Index(_, relation) = &mut stats.term_cnts {
+            *relation = Some(index);
+        }
+        stats
+    }
+
+    pub fn avgdl(&self) -> f32 {
+        self.doc_term_cnt as f32 / self.doc_cnt as f32
+    }
+}
+
+impl TermStatRead for Bm25Stats {
+    fn read(&self, term_id: u32) -> u32 {
+        match &self.term_cnts {
+            TermCnts::Index(reader, _) => reader.read(term_id),
+            TermCnts::Query(term_cnts) => term_cnts.get(&term_id).copied().unwrap_or(0),
+        }
+    }
+}
+
+// The statistics of a partitioned index for the terms of a query. The index scans of all
+// partitions and the `<&>` operator on every row run the same query, so they are computed once
+// per statement and shared, instead of reading the metapages of all partitions for each of them.
+#[derive(Clone)]
+pub struct PartitionedStats {
+    pub leaf_indexes: Vec<pgrx::pg_sys::Oid>,
+    doc_cnt: u32,
+    doc_term_cnt: u64,
+    term_cnts: BTreeMap<u32, u32>,
+}
+
+// the query and the statement the cached statistics are computed for
+#[derive(PartialEq)]
+struct StatsKey {
+    index_oid: pgrx::pg_sys::Oid,
+    tokenizer: Option<String>,
+    term_ids: Vec<u32>,
+    statement_start: pgrx::pg_sys::TimestampTz,
+    command_id: pgrx::pg_sys::CommandId,
+}
+
+// dropped at the end of each transaction, and when a subtransaction is aborted
+static PARTITIONED_STATS: PgRefCell<Option<(StatsKey, PartitionedStats)>> =
+    unsafe { PgRefCell::new(None) };
+
+unsafe extern "C" fn xact_callback(
+    event: pgrx::pg_sys::XactEvent::Type,
+    _arg: *mut std::ffi::c_void,
+) {
+    use pgrx::pg_sys::XactEvent::*;
+    if matches!(
+        event,
+        XACT_EVENT_COMMIT
+            | XACT_EVENT_PARALLEL_COMMIT
+            | XACT_EVENT_ABORT
+            | XACT_EVENT_PARALLEL_ABORT
+    ) {
+        *PARTITIONED_STATS.borrow_mut() = None;
+    }
+}
+
+unsafe extern "C" fn subxact_callback(
+    event: pgrx::pg_sys::SubXactEvent::Type,
+    _my_subid: pgrx::pg_sys::SubTransactionId,
+    _parent_subid: pgrx::pg_sys::SubTransactionId,
+    _arg: *mut std::ffi::c_void,
+) {
+    if event == pgrx::pg_sys::SubXactEvent::SUBXACT_EVENT_ABORT_SUB {
+        *PARTITIONED_STATS.borrow_mut() = None;
+    }
+}
+
+impl PartitionedStats {
+    pub fn of_query(
+        index_oid: pgrx::pg_sys::Oid,
+        tokenizer: Option<&str>,
+        query_vector: Bm25VectorBorrowed,
+    ) -> Self {
+        let key = StatsKey {
+            index_oid,
+            tokenizer: tokenizer.map(str::to_string),
+            term_ids: query_vector.indexes().to_vec(),
+            statement_start: unsafe { pgrx::pg_sys::GetCurrentStatementStartTimestamp() },
+            command_id: unsafe { pgrx::pg_sys::GetCurrentCommandId(false) },
+        };
+        if let Some((cached_key, stats)) = PARTITIONED_STATS.borrow().as_ref() {
+            if *cached_key == key {
+                return stats.clone();
+            }
+        }
+        let stats = Self::read(index_oid, tokenizer, query_vector);
+        *PARTITIONED_STATS.borrow_mut() = Some((key, stats.clone()));
+        stats
+    }
+
+    // the query tokenizer is checked against every partition
+    fn read(
+        index_oid: pgrx::pg_sys::Oid,
+        tokenizer: Option<&str>,
+        query_vector: Bm25VectorBorrowed,
+    ) -> Self {
+        let mut stats = Self {
+            leaf_indexes: leaf_indexes(index_oid),
+            doc_cnt: 0,
+            doc_term_cnt: 0,
+            term_cnts: query_vector.indexes().iter().map(|&id| (id, 0)).collect(),
+        };
+        for &oid in stats.leaf_indexes.iter() {
+            let partition =
+                unsafe { pgrx::PgRelation::with_lock(oid, pgrx::pg_sys::AccessShareLock as _) };
+            let page = metapage_read(partition.as_ptr());
+            let meta: &MetaPageData = page.as_ref();
+            check_query_tokenizer(partition.as_ptr(), meta, tokenizer);
+            stats.doc_cnt = stats.doc_cnt.saturating_add(meta.doc_cnt);
+            stats.doc_term_cnt += meta.doc_term_cnt;
+            let reader = TermStatReader::new(partition.as_ptr(), meta);
+            for (&term_id, term_cnt) in stats.term_cnts.iter_mut() {
+                *term_cnt = term_cnt.saturating_add(reader.read(term_id));
+            }
+        }
+        stats
+    }
+
+    pub fn to_stats(&self) -> Bm25Stats {
+        Bm25Stats {
+            doc_cnt: self.doc_cnt,
+            doc_term_cnt: self.doc_term_cnt,
+            term_cnts: TermCnts::Query(self.term_cnts.clone()),
+        }
+    }
+}
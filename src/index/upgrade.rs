@@ -0,0 +1,138 @@
+use pgrx::{iter::SetOfIterator, pg_sys::panic::ErrorReportable};
+
+use crate::{
+    page::PageData,
+    page::{page_read, page_write, METAPAGE_BLKNO},
+    segment::meta::{meta_version, MetaPageData, MetaPageDataV2, META_VERSION},
+};
+
+use super::check_bm25_index;
+
+// a migration rewrites the metapage, including its version
+type Migration = fn(pgrx::pg_sys::Relation, &mut PageData);
+
+// `MIGRATIONS[i]` upgrades an index from version `i + 1` to `i + 2` in place.
+// `None` means the format change cannot be applied in place and the index must be rebuilt.
+const MIGRATIONS: [Option<Migration>; META_VERSION as usize - 1] =
+    [Some(migrate_compact_growing), Some(migrate_metapage_header)];
+
+// the growing segment reader accepts the uncompressed items written by version 1,
+// so only the new items are compact
+fn migrate_compact_growing(_index: pgrx::pg_sys::Relation, page: &mut PageData) {
+    MetaPageDataV2::write_version(page, 2);
+}
+
+// the metapage is rewritten in the layout of `MetaPageData`, the other pages are unchanged
+fn migrate_metapage_header(_index: pgrx::pg_sys::Relation, page: &mut PageData) {
+    let legacy = MetaPageDataV2::decode(page);
+    let meta: &mut MetaPageData = page.as_mut();
+    *meta = legacy.upgrade();
+    page.header.pd_lower = (std::mem::size_of::<pgrx::pg_sys::PageHeaderData>()
+        + std::mem::size_of::<MetaPageData>()) as u16;
+}
+
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_index_format_version(index: pgrx::PgRelation) -> i32 {
+    check_bm25_index(&index);
+    let page = page_read(index.as_ptr(), METAPAGE_BLKNO);
+    meta_version(&page) as i32
+}
+
+// The index is taken by oid, so that it's locked exclusively before it's opened. Upgrading a
+// weaker lock could deadlock with another session doing the same.
+#[pgrx::pg_extern(sql = "\
+CREATE FUNCTION bm25_upgrade_index(index regclass) RETURNS text
+VOLATILE STRICT LANGUAGE c AS 'MODULE_PATHNAME', '@FUNCTION_NAME@';
+")]
+pub fn bm25_upgrade_index(index: pgrx::pg_sys::Oid) -> String {
+    // the lock is held until the end of the transaction, after the relation is closed
+    let index = unsafe {
+        pgrx::PgRelation::from_pg_owned(pgrx::pg_sys::relation_open(
+            index,
+            pgrx::pg_sys::AccessExclusiveLock as _,
+        ))
+    };
+    check_bm25_index(&index);
+    let qualified_name = pgrx::spi::quote_qualified_identifier(index.namespace(), index.name());
+    let mut metapage = page_write(index.as_ptr(), METAPAGE_BLKNO);
+    let from_version = meta_version(&metapage);
+    if from_version == META_VERSION {
+        return format!("{} is up to date", qualified_name);
+    }
+    if from_version > META_VERSION {
+        pgrx::error!(
+            "{} has on-disk format version {}, which is newer than the supported version {}",
+            qualified_name,
+            from_version,
+            META_VERSION
+        );
+    }
+
+    let mut version = from_version;
+    while version < META_VERSION {
+        let migration = version
+            .checked_sub(1)
+            .and_then(|i| MIGRATIONS.get(i as usize))
+            .copied()
+            .flatten();
+        let Some(migration) = migration else {
+            // REINDEX refuses to run while the relation is still open in this backend
+            drop(metapage);
+            drop(index);
+            pgrx::Spi::run(&format!("REINDEX INDEX {}", qualified_name)).unwrap_or_report();
+            return format!(
+                "{} is rebuilt from version {} to version {}",
+                qualified_name, from_version, META_VERSION
+            );
+        };
+        migration(index.as_ptr(), &mut metapage);
+        version = meta_version(&metapage);
+    }
+
+    format!(
+        "{} is upgraded in place from version {} to version {}",
+        qualified_name, from_version, META_VERSION
+    )
+}
+
+// A partitioned index has no storage, the indexes of its partitions are upgraded one by one.
+#[pgrx::pg_extern(volatile, strict)]
+pub fn bm25_upgrade_all_indexes() -> SetOfIterator<'static, String> {
+    let rows = pgrx::Spi::connect(|client| {
+        let query = r#"
+            SELECT c.oid, c.relkind = 'I' FROM pg_catalog.pg_class c
+            JOIN pg_catalog.pg_am a ON c.relam = a.oid
+            WHERE a.amname = 'bm25' AND c.relkind IN ('i', 'I')
+        "#;
+        let rows = client.select(query, None, None).unwrap_or_report();
+        rows.map(|row| {
+            let oid = row
+                .get::<pgrx::pg_sys::Oid>(1)
+                .unwrap_or_report()
+                .expect("no oid value");
+            let partitioned = row
+                .get::<bool>(2)
+                .unwrap_or_report()
+                .expect("no relkind value");
+            (oid, partitioned)
+        })
+        .collect::<Vec<_>>()
+    });
+    let results = rows
+        .into_iter()
+        .map(|(oid, partitioned)| {
+            if !partitioned {
+                return bm25_upgrade_index(oid);
+            }
+            let index =
+                unsafe { pgrx::PgRelation::with_lock(oid, pgrx::pg_sys::AccessShareLock as _) };
+            let qualified_name =
+                pgrx::spi::quote_qualified_identifier(index.namespace(), index.name());
+            format!(
+                "{} is partitioned, the indexes of its partitions are upgraded",
+                qualified_name
+            )
+        })
+        .collect::<Vec<_>>();
+    SetOfIterator::new(results)
+}
@@ -1,12 +1,13 @@
+use std::collections::HashSet;
+
 use lending_iterator::LendingIterator;
 
 use crate::{
-    page::{bm25_page_size, page_read, page_write, METAPAGE_BLKNO},
+    page::bm25_page_size,
     segment::{
         delete::DeleteBitmapReader,
-        field_norm::{FieldNormRead, FieldNormReader},
         growing::GrowingSegmentReader,
-        meta::MetaPageData,
+        meta::{metapage_read, metapage_write, MetaPageData},
         payload::PayloadReader,
         sealed::SealedSegmentReader,
         term_stat::TermStatReader,
@@ -38,11 +39,11 @@ pub unsafe extern "C" fn ambulkdelete(
     let stats = stats.as_mut().unwrap();
 
     let index = (*info).index;
-    let mut metapage = page_write(index, METAPAGE_BLKNO);
+    let mut metapage = metapage_write(index);
     let meta: &mut MetaPageData = metapage.as_mut();
     let payload_reader = PayloadReader::new(index, meta.payload_blkno);
-    let field_norm_reader = FieldNormReader::new(index, meta.field_norm_blkno);
     let mut delete_bitmap_reader = DeleteBitmapReader::new(index, meta.delete_bitmap_blkno);
+    let mut deleted = HashSet::new();
 
     for i in 0..meta.current_doc_id {
         if i % bm25_page_size() as u32 == 0 {
@@ -54,17 +55,68 @@ pub unsafe extern "C" fn ambulkdelete(
         let tid = payload_reader.read(i);
         if callback(tid) {
             delete_bitmap_reader.delete(i);
-            meta.doc_cnt -= 1;
-            meta.doc_term_cnt -= field_norm_reader.read(i) as u64;
+            deleted.insert(i);
             stats.tuples_removed += 1.0;
         } else {
             stats.num_index_tuples += 1.0;
         }
     }
 
+    if !deleted.is_empty() {
+        meta.doc_cnt -= deleted.len() as u32;
+        meta.doc_term_cnt -= deleted_doc_term_cnt(index, meta, &deleted);
+    }
+
     stats
 }
 
+// The field norms are quantized, so the exact length of the deleted documents is summed from the
+// vectors of the growing segment and the term frequencies of the sealed segment.
+fn deleted_doc_term_cnt(
+    index: pgrx::pg_sys::Relation,
+    meta: &MetaPageData,
+    deleted: &HashSet<u32>,
+) -> u64 {
+    let mut doc_term_cnt = 0u64;
+
+    if let Some(growing) = meta.growing_segment.as_ref() {
+        let reader = GrowingSegmentReader::new(index, growing);
+        let mut doc_id = meta.sealed_doc_id;
+        let mut iter = reader.into_lending_iter();
+        while let Some(vector) = iter.next() {
+            if deleted.contains(&doc_id) {
+                doc_term_cnt += vector.doc_len() as u64;
+            }
+            doc_id += 1;
+        }
+    }
+
+    if deleted.iter().any(|&doc_id| doc_id < meta.sealed_doc_id) {
+        let sealed_reader = SealedSegmentReader::new(index, meta.sealed_segment);
+        for i in 0..meta.sealed_segment.term_id_cnt {
+            let Some(mut posting) = sealed_reader.get_postings(i) else {
+                continue;
+            };
+            loop {
+                posting.decode_block();
+                loop {
+                    if deleted.contains(&posting.doc_id()) {
+                        doc_term_cnt += posting.term_freq() as u64;
+                    }
+                    if !posting.advance_cur() {
+                        break;
+                    }
+                }
+                if !posting.advance_block() {
+                    break;
+                }
+            }
+        }
+    }
+
+    doc_term_cnt
+}
+
 #[pgrx::pg_guard]
 pub unsafe extern "C" fn amvacuumcleanup(
     info: *mut pgrx::pg_sys::IndexVacuumInfo,
@@ -85,7 +137,7 @@ pub unsafe extern "C" fn amvacuumcleanup(
 
     let index = (*info).index;
 
-    let metapage = page_read(index, METAPAGE_BLKNO);
+    let metapage = metapage_read(index);
     let meta: &MetaPageData = metapage.as_ref();
     let term_id_cnt = meta.term_id_cnt;
     let mut term_stats = (0..term_id_cnt).map(|_| 0u32).collect::<Vec<_>>();
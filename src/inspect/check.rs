@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+
+use lending_iterator::LendingIterator;
+use pgrx::{
+    itemptr::{item_pointer_to_u64, u64_to_item_pointer_parts},
+    PgSqlErrorCode,
+};
+
+use crate::{
+    index::check_bm25_index,
+    page::{page_read, METAPAGE_BLKNO},
+    segment::{
+        delete::DeleteBitmapReader,
+        field_norm::{fieldnorm_to_id, id_to_fieldnorm, FieldNormRead, FieldNormReader},
+        growing::GrowingSegmentReader,
+        meta::{meta_version, MetaPageData, META_VERSION},
+        payload::PayloadReader,
+        sealed::SealedSegmentReader,
+        term_stat::{TermStatRead, TermStatReader},
+    },
+};
+
+fn report_corruption(index_name: &str, detail: String) -> ! {
+    pgrx::ereport!(
+        ERROR,
+        PgSqlErrorCode::ERRCODE_INDEX_CORRUPTED,
+        format!("bm25 index \"{}\" is corrupted", index_name),
+        detail
+    );
+}
+
+/// Verify the internal consistency of a bm25 index, raising an error on the first corruption found.
+///
+/// With `heapallindexed`, it also checks that every heap tuple visible to the index build scan has
+/// its ctid in the payload segment. It takes a `ShareLock` on the table, which blocks writes until
+/// the end of the transaction.
+#[pgrx::pg_extern(volatile, strict)]
+pub fn bm25_index_check(index: pgrx::PgRelation, heapallindexed: pgrx::default!(bool, false)) {
+    check_bm25_index(&index);
+    let index_name = index.name().to_string();
+    let heap = heapallindexed.then(|| unsafe {
+        pgrx::PgRelation::with_lock((*index.rd_index).indrelid, pgrx::pg_sys::ShareLock as _)
+    });
+
+    let live_ctids = check_segments(index.as_ptr(), &index_name, heapallindexed);
+
+    if let Some(heap) = heap {
+        check_heap_all_indexed(heap.as_ptr(), index.as_ptr(), &index_name, live_ctids);
+    }
+}
+
+// the metapage is locked during the check, so that inserts and vacuum cannot run concurrently
+fn check_segments(
+    index: pgrx::pg_sys::Relation,
+    index_name: &str,
+    collect_ctids: bool,
+) -> HashSet<u64> {
+    let page = page_read(index, METAPAGE_BLKNO);
+    let version = meta_version(&page);
+    if version != META_VERSION {
+        report_corruption(
+            index_name,
+            format!("metapage version is {}, expected {}", version, META_VERSION),
+        );
+    }
+    let meta: &MetaPageData = page.as_ref();
+    if meta.sealed_doc_id > meta.current_doc_id {
+        report_corruption(
+            index_name,
+            format!(
+                "sealed_doc_id {} is greater than current_doc_id {}",
+                meta.sealed_doc_id, meta.current_doc_id
+            ),
+        );
+    }
+    if meta.sealed_segment.term_id_cnt > meta.term_id_cnt {
+        report_corruption(
+            index_name,
+            format!(
+                "sealed segment has {} terms, but the index only has {}",
+                meta.sealed_segment.term_id_cnt, meta.term_id_cnt
+            ),
+        );
+    }
+
+    let delete_bitmap_reader = DeleteBitmapReader::new(index, meta.delete_bitmap_blkno);
+    let field_norm_reader = FieldNormReader::new(index, meta.field_norm_blkno);
+    let payload_reader = PayloadReader::new(index, meta.payload_blkno);
+
+    // field norms are quantized, so only the bounds of `doc_term_cnt` are known
+    let mut live_ctids = HashSet::new();
+    let mut live_doc_cnt = 0u32;
+    let mut min_doc_term_cnt = 0u64;
+    let mut max_doc_term_cnt = 0u64;
+    for doc_id in 0..meta.current_doc_id {
+        if delete_bitmap_reader.is_delete(doc_id) {
+            continue;
+        }
+        let fieldnorm_id = field_norm_reader.read(doc_id);
+        live_doc_cnt += 1;
+        min_doc_term_cnt += id_to_fieldnorm(fieldnorm_id) as u64;
+        max_doc_term_cnt += match fieldnorm_id {
+            u8::MAX => u32::MAX as u64,
+            _ => id_to_fieldnorm(fieldnorm_id + 1) as u64 - 1,
+        };
+        if collect_ctids {
+            live_ctids.insert(payload_reader.read(doc_id));
+        }
+    }
+    if meta.doc_cnt != live_doc_cnt {
+        report_corruption(
+            index_name,
+            format!(
+                "doc_cnt is {}, but {} documents are not deleted",
+                meta.doc_cnt, live_doc_cnt
+            ),
+        );
+    }
+    if !(min_doc_term_cnt..=max_doc_term_cnt).contains(&meta.doc_term_cnt) {
+        report_corruption(
+            index_name,
+            format!(
+                "doc_term_cnt is {}, but field norms require it to be between {} and {}",
+                meta.doc_term_cnt, min_doc_term_cnt, max_doc_term_cnt
+            ),
+        );
+    }
+
+    let mut doc_freqs = vec![0u32; meta.term_id_cnt as usize];
+
+    let mut doc_id = meta.sealed_doc_id;
+    if let Some(growing) = meta.growing_segment.as_ref() {
+        let reader = GrowingSegmentReader::new(index, growing);
+        let mut iter = reader.into_lending_iter();
+        while let Some(vector) = iter.next() {
+            if doc_id >= meta.current_doc_id {
+                report_corruption(
+                    index_name,
+                    format!(
+                        "growing segment contains more documents than current_doc_id {}",
+                        meta.current_doc_id
+                    ),
+                );
+            }
+            let fieldnorm_id = field_norm_reader.read(doc_id);
+            if fieldnorm_id != fieldnorm_to_id(vector.doc_len()) {
+                report_corruption(
+                    index_name,
+                    format!(
+                        "document {} has length {}, but its field norm id is {}",
+                        doc_id,
+                        vector.doc_len(),
+                        fieldnorm_id
+                    ),
+                );
+            }
+            let deleted = delete_bitmap_reader.is_delete(doc_id);
+            for &term_id in vector.indexes() {
+                if term_id >= meta.term_id_cnt {
+                    report_corruption(
+                        index_name,
+                        format!(
+                            "document {} contains term {}, but the index only has {} terms",
+                            doc_id, term_id, meta.term_id_cnt
+                        ),
+                    );
+                }
+                if !deleted {
+                    doc_freqs[term_id as usize] += 1;
+                }
+            }
+            doc_id += 1;
+        }
+    }
+    if doc_id != meta.current_doc_id {
+        report_corruption(
+            index_name,
+            format!(
+                "sealed and growing segments contain {} documents, but current_doc_id is {}",
+                doc_id, meta.current_doc_id
+            ),
+        );
+    }
+
+    let sealed_reader = SealedSegmentReader::new(index, meta.sealed_segment);
+    for term_id in 0..meta.sealed_segment.term_id_cnt {
+        let Some(mut posting) = sealed_reader.get_postings(term_id) else {
+            continue;
+        };
+        let mut prev_doc_id = None;
+        let mut block = 0;
+        loop {
+            posting.decode_block();
+            let skip = *posting.skip_block();
+            let (blockwand_tf, blockwand_fieldnorm_id) =
+                (skip.blockwand_tf(), skip.blockwand_fieldnorm_id());
+            let mut blockwand_found = false;
+            loop {
+                let doc_id = posting.doc_id();
+                let tf = posting.term_freq();
+                let fieldnorm_id = field_norm_reader.read(doc_id);
+                if prev_doc_id.is_some_and(|prev| doc_id <= prev) {
+                    report_corruption(
+                        index_name,
+                        format!(
+                            "posting list of term {} is not strictly increasing in block {}: document {} follows document {}",
+                            term_id, block, doc_id, prev_doc_id.unwrap()
+                        ),
+                    );
+                }
+                if doc_id >= meta.sealed_doc_id {
+                    report_corruption(
+                        index_name,
+                        format!(
+                            "posting list of term {} contains document {}, but sealed_doc_id is {}",
+                            term_id, doc_id, meta.sealed_doc_id
+                        ),
+                    );
+                }
+                if tf == 0 {
+                    report_corruption(
+                        index_name,
+                        format!(
+                            "posting list of term {} contains document {} with zero term frequency",
+                            term_id, doc_id
+                        ),
+                    );
+                }
+                // no document in the block may beat the block-max entry regardless of the weight
+                if tf == blockwand_tf && fieldnorm_id == blockwand_fieldnorm_id {
+                    blockwand_found = true;
+                } else if tf >= blockwand_tf && fieldnorm_id <= blockwand_fieldnorm_id {
+                    report_corruption(
+                        index_name,
+                        format!(
+                            "document {} (tf {}, field norm id {}) in block {} of term {} outscores the block-max entry (tf {}, field norm id {})",
+                            doc_id, tf, fieldnorm_id, block, term_id, blockwand_tf, blockwand_fieldnorm_id
+                        ),
+                    );
+                }
+                if !delete_bitmap_reader.is_delete(doc_id) {
+                    doc_freqs[term_id as usize] += 1;
+                }
+                prev_doc_id = Some(doc_id);
+                if !posting.advance_cur() {
+                    break;
+                }
+            }
+            if prev_doc_id != Some(skip.last_doc()) {
+                report_corruption(
+                    index_name,
+                    format!(
+                        "block {} of term {} ends with document {}, but its skip block says {}",
+                        block,
+                        term_id,
+                        prev_doc_id.unwrap(),
+                        skip.last_doc()
+                    ),
+                );
+            }
+            if !blockwand_found {
+                report_corruption(
+                    index_name,
+                    format!(
+                        "block-max entry (tf {}, field norm id {}) of block {} of term {} matches no document",
+                        blockwand_tf, blockwand_fieldnorm_id, block, term_id
+                    ),
+                );
+            }
+            if !posting.advance_block() {
+                break;
+            }
+            block += 1;
+        }
+    }
+
+    let term_stat_reader = TermStatReader::new(index, meta);
+    for (term_id, &doc_freq) in doc_freqs.iter().enumerate() {
+        let stored = term_stat_reader.read(term_id as u32);
+        if stored != doc_freq {
+            report_corruption(
+                index_name,
+                format!(
+                    "doc frequency of term {} is {}, but {} live documents contain it",
+                    term_id, stored, doc_freq
+                ),
+            );
+        }
+    }
+
+    live_ctids
+}
+
+struct HeapCheckState {
+    live_ctids: HashSet<u64>,
+    missing: Option<u64>,
+}
+
+unsafe extern "C" fn heap_check_callback(
+    _index: pgrx::pg_sys::Relation,
+    ctid: pgrx::pg_sys::ItemPointer,
+    _datum: *mut pgrx::pg_sys::Datum,
+    is_null: *mut bool,
+    _tuple_is_alive: bool,
+    state: *mut std::os::raw::c_void,
+) {
+    let state = &mut *(state.cast::<HeapCheckState>());
+    // null values are never inserted into the index
+    if *is_null || state.missing.is_some() {
+        return;
+    }
+    let tid = item_pointer_to_u64(ctid.read());
+    if !state.live_ctids.contains(&tid) {
+        state.missing = Some(tid);
+    }
+}
+
+fn check_heap_all_indexed(
+    heap: pgrx::pg_sys::Relation,
+    index: pgrx::pg_sys::Relation,
+    index_name: &str,
+    live_ctids: HashSet<u64>,
+) {
+    let mut state = HeapCheckState {
+        live_ctids,
+        missing: None,
+    };
+    unsafe {
+        let index_info = pgrx::pg_sys::BuildIndexInfo(index);
+        pgrx::pg_sys::IndexBuildHeapScan(
+            heap,
+            index,
+            index_info,
+            Some(heap_check_callback),
+            &mut state,
+        );
+    }
+    if let Some(tid) = state.missing {
+        let (blkno, offno) = u64_to_item_pointer_parts(tid);
+        report_corruption(
+            index_name,
+            format!(
+                "heap tuple ({},{}) is not present in the index",
+                blkno, offno
+            ),
+        );
+    }
+}
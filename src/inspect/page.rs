@@ -0,0 +1,250 @@
+use pgrx::{iter::TableIterator, name};
+
+use crate::{
+    index::check_bm25_index,
+    page::{
+        page_read, PageData, PageFlags, PageReadGuard, VirtualPageKind, VirtualPageReader,
+        BM25_PAGE_ID, METAPAGE_BLKNO,
+    },
+    segment::meta::{meta_version, MetaPageData, MetaPageDataV2},
+};
+
+fn block_count(index: pgrx::pg_sys::Relation) -> u32 {
+    unsafe {
+        pgrx::pg_sys::RelationGetNumberOfBlocksInFork(index, pgrx::pg_sys::ForkNumber::MAIN_FORKNUM)
+    }
+}
+
+fn read_block(index: pgrx::pg_sys::Relation, blkno: i64) -> PageReadGuard {
+    let nblocks = block_count(index);
+    match u32::try_from(blkno) {
+        Ok(blkno) if blkno < nblocks => page_read(index, blkno),
+        _ => pgrx::error!(
+            "block number {} is out of range, the index has {} blocks",
+            blkno,
+            nblocks
+        ),
+    }
+}
+
+// A block extended but not initialized yet, e.g. by a crash before its first write, is all zeros.
+// Its header and opaque data are not meaningful, so it's shown as empty.
+fn is_new(page: &PageData) -> bool {
+    page.header.pd_upper == 0
+}
+
+fn flags_to_string(flag: PageFlags) -> String {
+    let mut s = String::new();
+    bitflags::parser::to_writer(&flag, &mut s).unwrap();
+    s
+}
+
+fn blkno_to_i64(blkno: pgrx::pg_sys::BlockNumber) -> Option<i64> {
+    (blkno != pgrx::pg_sys::InvalidBlockNumber).then_some(blkno as i64)
+}
+
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+#[allow(clippy::type_complexity)]
+pub fn bm25_page_header(
+    index: pgrx::PgRelation,
+    blkno: i64,
+) -> TableIterator<
+    'static,
+    (
+        name!(lsn, String),
+        name!(checksum, i32),
+        name!(flags, i32),
+        name!(lower, i32),
+        name!(upper, i32),
+        name!(special, i32),
+        name!(pagesize, i32),
+        name!(version, i32),
+        name!(page_flag, String),
+        name!(next_blkno, Option<i64>),
+        name!(bm25_page_id, i32),
+    ),
+> {
+    check_bm25_index(&index);
+    let page = read_block(index.as_ptr(), blkno);
+    let header = &page.header;
+    let new = is_new(&page);
+    if !new && page.opaque.bm25_page_id() != BM25_PAGE_ID {
+        pgrx::warning!(
+            "block {} has page id {:#06X}, expected {:#06X}",
+            blkno,
+            page.opaque.bm25_page_id(),
+            BM25_PAGE_ID
+        );
+    }
+    TableIterator::once((
+        format!("{:X}/{:X}", header.pd_lsn.xlogid, header.pd_lsn.xrecoff),
+        header.pd_checksum as i32,
+        header.pd_flags as i32,
+        header.pd_lower as i32,
+        header.pd_upper as i32,
+        header.pd_special as i32,
+        (header.pd_pagesize_version & 0xFF00) as i32,
+        (header.pd_pagesize_version & 0x00FF) as i32,
+        flags_to_string(page.opaque.page_flag),
+        blkno_to_i64(page.opaque.next_blkno).filter(|_| !new),
+        page.opaque.bm25_page_id() as i32,
+    ))
+}
+
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_page_data(index: pgrx::PgRelation, blkno: i64) -> Vec<u8> {
+    check_bm25_index(&index);
+    let page = read_block(index.as_ptr(), blkno);
+    if is_new(&page) {
+        return Vec::new();
+    }
+    page.data().to_vec()
+}
+
+// follow `next_blkno` from the given block until the end of the chain
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+#[allow(clippy::type_complexity)]
+pub fn bm25_page_chain(
+    index: pgrx::PgRelation,
+    blkno: i64,
+) -> TableIterator<
+    'static,
+    (
+        name!(blkno, i64),
+        name!(page_flag, String),
+        name!(next_blkno, Option<i64>),
+        name!(data_size, i32),
+        name!(free_size, i32),
+    ),
+> {
+    check_bm25_index(&index);
+    let index = index.as_ptr();
+    let nblocks = block_count(index);
+    let mut rows = Vec::new();
+    let mut current = blkno;
+    loop {
+        if rows.len() > nblocks as usize {
+            pgrx::error!("page chain starting at block {} contains a cycle", blkno);
+        }
+        let page = read_block(index, current);
+        if is_new(&page) {
+            rows.push((current, String::new(), None, 0, 0));
+            break;
+        }
+        let next_blkno = blkno_to_i64(page.opaque.next_blkno);
+        rows.push((
+            current,
+            flags_to_string(page.opaque.page_flag),
+            next_blkno,
+            page.data().len() as i32,
+            page.header.pd_upper.saturating_sub(page.header.pd_lower) as i32,
+        ));
+        match next_blkno {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+    TableIterator::new(rows)
+}
+
+// resolve the logical-to-physical block map of the virtual page file whose direct inode is `blkno`
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_virtual_page_map(
+    index: pgrx::PgRelation,
+    blkno: i64,
+) -> TableIterator<
+    'static,
+    (
+        name!(blkno, i64),
+        name!(kind, String),
+        name!(virtual_id, Option<i64>),
+    ),
+> {
+    check_bm25_index(&index);
+    let index = index.as_ptr();
+    let page = read_block(index, blkno);
+    if page.opaque.page_flag.contains(PageFlags::META) {
+        pgrx::error!("block {} is the metapage", blkno);
+    }
+    drop(page);
+    let reader = VirtualPageReader::new(index, blkno as u32);
+    let rows = reader
+        .blocks()
+        .into_iter()
+        .map(|(kind, blkno)| {
+            let (kind, virtual_id) = match kind {
+                VirtualPageKind::DirectInode => ("direct_inode", None),
+                VirtualPageKind::Indirect1Inode => ("indirect1_inode", None),
+                VirtualPageKind::Indirect1Page => ("indirect1_page", None),
+                VirtualPageKind::Indirect2Inode => ("indirect2_inode", None),
+                VirtualPageKind::Indirect2Page => ("indirect2_page", None),
+                VirtualPageKind::Data(id) => ("data", Some(id as i64)),
+            };
+            (blkno as i64, kind.to_string(), virtual_id)
+        })
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+#[allow(clippy::type_complexity)]
+pub fn bm25_metapage(
+    index: pgrx::PgRelation,
+) -> TableIterator<
+    'static,
+    (
+        name!(version, i64),
+        name!(doc_cnt, i64),
+        name!(doc_term_cnt, i64),
+        name!(term_id_cnt, i64),
+        name!(sealed_doc_id, i64),
+        name!(current_doc_id, i64),
+        name!(field_norm_blkno, i64),
+        name!(payload_blkno, i64),
+        name!(term_stat_blkno, i64),
+        name!(delete_bitmap_blkno, i64),
+        name!(growing_first_blkno, Option<i64>),
+        name!(growing_last_blkno, Option<i64>),
+        name!(growing_full_page_count, Option<i64>),
+        name!(term_info_blkno, Option<i64>),
+        name!(sealed_term_id_cnt, i64),
+        name!(tokenizer, Option<String>),
+        name!(tokenizer_version, Option<i64>),
+    ),
+> {
+    check_bm25_index(&index);
+    let page = page_read(index.as_ptr(), METAPAGE_BLKNO);
+    let version = meta_version(&page);
+    // the metapage of an index not upgraded yet is shown in the current layout
+    let meta: MetaPageData = if version < 3 {
+        MetaPageData {
+            version,
+            ..MetaPageDataV2::decode(&page).upgrade()
+        }
+    } else {
+        let meta: &MetaPageData = page.as_ref();
+        *meta
+    };
+    let growing = meta.growing_segment.as_ref();
+    let tokenizer = &meta.tokenizer;
+    let tokenizer_name = tokenizer.name();
+    TableIterator::once((
+        meta.version as i64,
+        meta.doc_cnt as i64,
+        meta.doc_term_cnt as i64,
+        meta.term_id_cnt as i64,
+        meta.sealed_doc_id as i64,
+        meta.current_doc_id as i64,
+        meta.field_norm_blkno as i64,
+        meta.payload_blkno as i64,
+        meta.term_stat_blkno as i64,
+        meta.delete_bitmap_blkno as i64,
+        growing.map(|g| g.first_blkno.get() as i64),
+        growing.and_then(|g| blkno_to_i64(g.last_blkno)),
+        growing.map(|g| g.growing_full_page_count as i64),
+        blkno_to_i64(meta.sealed_segment.term_info_blkno),
+        meta.sealed_segment.term_id_cnt as i64,
+        tokenizer_name.map(str::to_string),
+        tokenizer_name.map(|_| tokenizer.version as i64),
+    ))
+}
@@ -0,0 +1,114 @@
+use pgrx::{iter::TableIterator, name};
+
+use crate::{
+    index::check_bm25_index,
+    segment::{
+        delete::DeleteBitmapReader,
+        meta::{metapage_read, MetaPageData},
+        payload::PayloadReader,
+        posting::PostingTermInfoReader,
+        sealed::SealedSegmentReader,
+        term_stat::{TermStatRead, TermStatReader},
+    },
+};
+
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+pub fn bm25_term_stats(
+    index: pgrx::PgRelation,
+) -> TableIterator<
+    'static,
+    (
+        name!(term_id, i64),
+        name!(doc_freq, i64),
+        name!(sealed_doc_cnt, i64),
+    ),
+> {
+    check_bm25_index(&index);
+    let index = index.as_ptr();
+    let page = metapage_read(index);
+    let meta: &MetaPageData = page.as_ref();
+
+    let term_stat_reader = TermStatReader::new(index, meta);
+    let term_info_reader = PostingTermInfoReader::new(index, meta.sealed_segment);
+    let rows = (0..meta.term_id_cnt)
+        .map(|term_id| {
+            let doc_freq = term_stat_reader.read(term_id);
+            let sealed_doc_cnt = term_info_reader.read(term_id).doc_count;
+            (term_id as i64, doc_freq as i64, sealed_doc_cnt as i64)
+        })
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+// only the sealed segment is walked, documents in the growing segment have no posting list
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+#[allow(clippy::type_complexity)]
+pub fn bm25_postings(
+    index: pgrx::PgRelation,
+    term_id: i64,
+) -> TableIterator<
+    'static,
+    (
+        name!(doc_id, i64),
+        name!(tf, i64),
+        name!(ctid, pgrx::pg_sys::ItemPointerData),
+        name!(deleted, bool),
+        name!(block, i32),
+        name!(last_doc, i64),
+        name!(blockwand_tf, i64),
+        name!(blockwand_fieldnorm_id, i32),
+        name!(docid_bits, Option<i32>),
+        name!(tf_bits, Option<i32>),
+    ),
+> {
+    check_bm25_index(&index);
+    let Ok(term_id) = u32::try_from(term_id) else {
+        pgrx::error!("term_id {} is out of range", term_id);
+    };
+    let index = index.as_ptr();
+    let page = metapage_read(index);
+    let meta: &MetaPageData = page.as_ref();
+
+    let mut rows = Vec::new();
+    let sealed_reader = SealedSegmentReader::new(index, meta.sealed_segment);
+    let Some(mut posting) = sealed_reader.get_postings(term_id) else {
+        return TableIterator::new(rows);
+    };
+    let delete_bitmap_reader = DeleteBitmapReader::new(index, meta.delete_bitmap_blkno);
+    let payload_reader = PayloadReader::new(index, meta.payload_blkno);
+
+    let mut block = 0;
+    loop {
+        posting.decode_block();
+        let skip = *posting.skip_block();
+        let (docid_bits, tf_bits) = match skip.bits() {
+            Some((docid_bits, tf_bits)) => (Some(docid_bits as i32), Some(tf_bits as i32)),
+            None => (None, None),
+        };
+        loop {
+            let doc_id = posting.doc_id();
+            let mut ctid = pgrx::pg_sys::ItemPointerData::default();
+            pgrx::itemptr::u64_to_item_pointer(payload_reader.read(doc_id), &mut ctid);
+            rows.push((
+                doc_id as i64,
+                posting.term_freq() as i64,
+                ctid,
+                delete_bitmap_reader.is_delete(doc_id),
+                block,
+                skip.last_doc() as i64,
+                skip.blockwand_tf() as i64,
+                skip.blockwand_fieldnorm_id() as i32,
+                docid_bits,
+                tf_bits,
+            ));
+            if !posting.advance_cur() {
+                break;
+            }
+        }
+        if !posting.advance_block() {
+            break;
+        }
+        block += 1;
+    }
+    TableIterator::new(rows)
+}
@@ -4,6 +4,7 @@ pub mod algorithm;
 pub mod datatype;
 pub mod guc;
 pub mod index;
+pub mod inspect;
 pub mod page;
 pub mod segment;
 pub mod token;
@@ -24,6 +25,7 @@ compiler_error!("PostgreSQL version must be selected.");
 unsafe extern "C" fn _PG_init() {
     index::init();
     guc::init();
+    token::init();
 }
 
 // const SCHEMA: &str = "bm25_catalog";
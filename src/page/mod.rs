@@ -4,6 +4,6 @@ mod r#virtual;
 mod writer;
 
 pub use postgres::*;
-pub use r#virtual::{VirtualPageReader, VirtualPageWriter};
+pub use r#virtual::{VirtualPageKind, VirtualPageReader, VirtualPageWriter};
 pub use reader::{ContinuousPageReader, PageReader};
 pub use writer::{PageWriter, PageWriterInitFork};
@@ -4,8 +4,8 @@ use std::{
 };
 
 const _: () = {
-    assert!(std::mem::size_of::<pgrx::pg_sys::PageHeaderData>() % 8 == 0);
-    assert!(std::mem::size_of::<Bm25PageOpaqueData>() % 8 == 0);
+    assert!(std::mem::size_of::<pgrx::pg_sys::PageHeaderData>().is_multiple_of(8));
+    assert!(std::mem::size_of::<Bm25PageOpaqueData>().is_multiple_of(8));
     assert!(std::mem::size_of::<PageData>() == pgrx::pg_sys::BLCKSZ as usize);
 };
 
@@ -42,6 +42,12 @@ pub struct Bm25PageOpaqueData {
     bm25_page_id: u16, // for identification of bm25 index
 }
 
+impl Bm25PageOpaqueData {
+    pub fn bm25_page_id(&self) -> u16 {
+        self.bm25_page_id
+    }
+}
+
 #[repr(C, align(8))]
 pub struct PageData {
     pub header: pgrx::pg_sys::PageHeaderData,
@@ -1,14 +1,25 @@
 use super::{
-    bm25_page_size, page_alloc_init_forknum, page_alloc_with_fsm, page_read, page_write, PageFlags,
-    PageWriteGuard,
+    bm25_page_size, page_alloc_init_forknum, page_alloc_with_fsm, page_read, page_write, PageData,
+    PageFlags, PageWriteGuard,
 };
 
 const DIRECT_COUNT: usize = bm25_page_size() / 4;
 const INDIRECT1_COUNT: usize = DIRECT_COUNT * DIRECT_COUNT;
 const INDIRECT2_COUNT: usize = INDIRECT1_COUNT * DIRECT_COUNT;
 
+#[derive(Debug, Clone, Copy)]
+pub enum VirtualPageKind {
+    DirectInode,
+    Indirect1Inode,
+    Indirect1Page,
+    Indirect2Inode,
+    Indirect2Page,
+    Data(u32),
+}
+
 pub struct VirtualPageReader {
     relation: pgrx::pg_sys::Relation,
+    direct_inode_blkno: u32,
     direct_inode: Box<[u32]>,
     indirect1_inode_blkno: u32,
 }
@@ -25,6 +36,7 @@ impl VirtualPageReader {
 
         Self {
             relation,
+            direct_inode_blkno: blkno,
             direct_inode,
             indirect1_inode_blkno,
         }
@@ -84,6 +96,59 @@ impl VirtualPageReader {
         let slice = &indirect.data()[indirect1_offset * 4..][..4];
         u32::from_le_bytes(slice.try_into().unwrap())
     }
+
+    // all blocks owned by the virtual page file, inodes included, data pages in logical order
+    pub fn blocks(&self) -> Vec<(VirtualPageKind, pgrx::pg_sys::BlockNumber)> {
+        fn entries(page: &PageData) -> Vec<u32> {
+            page.data()
+                .chunks_exact(4)
+                .map(|s| u32::from_le_bytes(s.try_into().unwrap()))
+                .collect()
+        }
+
+        let mut blocks = vec![(VirtualPageKind::DirectInode, self.direct_inode_blkno)];
+        let mut virtual_id = 0;
+        let mut push_data = |blocks: &mut Vec<_>, blkno| {
+            blocks.push((VirtualPageKind::Data(virtual_id), blkno));
+            virtual_id += 1;
+        };
+        for &blkno in self.direct_inode.iter() {
+            push_data(&mut blocks, blkno);
+        }
+        if self.indirect1_inode_blkno == pgrx::pg_sys::InvalidBlockNumber {
+            return blocks;
+        }
+
+        let indirect1_inode = page_read(self.relation, self.indirect1_inode_blkno);
+        blocks.push((VirtualPageKind::Indirect1Inode, self.indirect1_inode_blkno));
+        for indirect1_blkno in entries(&indirect1_inode) {
+            blocks.push((VirtualPageKind::Indirect1Page, indirect1_blkno));
+            let indirect1 = page_read(self.relation, indirect1_blkno);
+            for blkno in entries(&indirect1) {
+                push_data(&mut blocks, blkno);
+            }
+        }
+        let indirect2_inode_blkno = indirect1_inode.opaque.next_blkno;
+        drop(indirect1_inode);
+        if indirect2_inode_blkno == pgrx::pg_sys::InvalidBlockNumber {
+            return blocks;
+        }
+
+        let indirect2_inode = page_read(self.relation, indirect2_inode_blkno);
+        blocks.push((VirtualPageKind::Indirect2Inode, indirect2_inode_blkno));
+        for indirect2_blkno in entries(&indirect2_inode) {
+            blocks.push((VirtualPageKind::Indirect2Page, indirect2_blkno));
+            let indirect2 = page_read(self.relation, indirect2_blkno);
+            for indirect1_blkno in entries(&indirect2) {
+                blocks.push((VirtualPageKind::Indirect1Page, indirect1_blkno));
+                let indirect1 = page_read(self.relation, indirect1_blkno);
+                for blkno in entries(&indirect1) {
+                    push_data(&mut blocks, blkno);
+                }
+            }
+        }
+        blocks
+    }
 }
 
 enum VirtualPageWriterState {
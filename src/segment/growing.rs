@@ -3,7 +3,7 @@ use std::num::NonZero;
 use lending_iterator::{lending_iterator::LendingIteratorඞItem, LendingIterator, HKT};
 
 use crate::{
-    datatype::{Bm25VectorBorrowed, Bm25VectorHeader, Bm25VectorInput},
+    datatype::{decode_compact, Bm25VectorBorrowed, Bm25VectorHeader, Bm25VectorInput},
     guc::SEGMENT_GROWING_MAX_PAGE_SIZE,
     page::{
         page_alloc_with_fsm, page_append_item, page_get_item, page_get_item_id,
@@ -14,6 +14,7 @@ use crate::{
 use super::meta::MetaPageData;
 
 /// store bm25vector
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct GrowingSegmentData {
     pub first_blkno: NonZero<u32>,
@@ -43,6 +44,9 @@ impl GrowingSegmentReader {
             page: Option<PageReadGuard>,
             offset: u16,
             count: u16,
+            // the terms of the last compact item
+            indexes: Vec<u32>,
+            values: Vec<u32>,
         }
 
         impl TmpState {
@@ -60,6 +64,8 @@ impl GrowingSegmentReader {
             page: Some(page),
             offset: 1,
             count,
+            indexes: Vec::new(),
+            values: Vec::new(),
         };
 
         lending_iterator::from_fn::<HKT!(Bm25VectorBorrowed<'_>), _, _>(state, |state| {
@@ -79,8 +85,22 @@ impl GrowingSegmentReader {
             let offset = state.offset;
             state.offset += 1;
             let item_id = page_get_item_id(state.page(), offset);
-            let item: &Bm25VectorHeader = page_get_item(state.page(), item_id);
-            Some(item.borrow())
+            // A growing segment mixes both layouts: the items written before version 2 and the
+            // small vectors are in the uncompressed layout, the others are compact. They're told
+            // apart by the flag in `len`, and `decode_compact` returns `None` for an uncompressed
+            // item, which is then checked to have exactly the size of its `len`, so a compact item
+            // with a corrupt flag errors out instead of being read as the uncompressed layout.
+            let item: &u8 = page_get_item(state.page(), item_id);
+            match unsafe { decode_compact(item, &mut state.indexes, &mut state.values) } {
+                Some(doc_len) => Some(unsafe {
+                    Bm25VectorBorrowed::new_unchecked(doc_len, &state.indexes, &state.values)
+                }),
+                None => {
+                    let item: &Bm25VectorHeader = page_get_item(state.page(), item_id);
+                    item.check();
+                    Some(item.borrow())
+                }
+            }
         })
     }
 }
@@ -95,8 +115,7 @@ pub fn growing_segment_insert(
     meta: &mut MetaPageData,
     bm25vector: &Bm25VectorInput,
 ) -> Option<u32> {
-    let mut buf: Vec<u8> = Vec::new();
-    buf.extend_from_slice(bm25vector.to_bytes());
+    let buf = bm25vector.encode();
 
     let Some(growing_segment) = &mut meta.growing_segment else {
         let mut page = page_alloc_with_fsm(index, PageFlags::GROWING, false);
@@ -1,9 +1,27 @@
+use crate::page::{page_read, page_write, PageData, PageReadGuard, PageWriteGuard, METAPAGE_BLKNO};
+
 use super::{growing::GrowingSegmentData, sealed::SealedSegmentData};
 
-pub const META_VERSION: u32 = 1;
+// 2: the items of the growing segment are in the compact layout of bm25vector
+// 3: the tokenizer the index is built with is recorded, the metapage starts with `MetaPageHeader`
+pub const META_VERSION: u32 = 3;
+
+// "BM25" in little endian, it marks a metapage that starts with `MetaPageHeader`
+pub const META_MAGIC: u32 = u32::from_le_bytes(*b"BM25");
+
+// The first bytes of the metapage since version 3, so the version is read without knowing the
+// layout of the rest of the page. The fields of `MetaPageData` are only appended after it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MetaPageHeader {
+    pub magic: u32,
+    pub version: u32,
+}
 
-#[derive(Debug)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct MetaPageData {
+    pub magic: u32,
     pub version: u32,
     pub doc_cnt: u32,
     pub doc_term_cnt: u64,
@@ -16,6 +34,162 @@ pub struct MetaPageData {
     pub delete_bitmap_blkno: u32,
     pub growing_segment: Option<GrowingSegmentData>,
     pub sealed_segment: SealedSegmentData,
+    pub tokenizer: TokenizerTag,
+}
+
+const _: () = {
+    assert!(std::mem::offset_of!(MetaPageData, magic) == 0);
+    assert!(std::mem::offset_of!(MetaPageData, version) == 4);
+};
+
+// The metapage of versions 1 and 2. `MetaPageData` had the default representation then, whose
+// layout isn't guaranteed by rustc, so it's decoded from the byte offsets of the fields written by
+// those versions instead of being cast. `growing_first_blkno` is 0 if there is no growing segment.
+#[derive(Debug, Clone, Copy)]
+pub struct MetaPageDataV2 {
+    pub doc_term_cnt: u64,
+    pub sealed_term_info_blkno: u32,
+    pub sealed_term_id_cnt: u32,
+    pub version: u32,
+    pub doc_cnt: u32,
+    pub term_id_cnt: u32,
+    pub sealed_doc_id: u32,
+    pub current_doc_id: u32,
+    pub field_norm_blkno: u32,
+    pub payload_blkno: u32,
+    pub term_stat_blkno: u32,
+    pub delete_bitmap_blkno: u32,
+    pub growing_first_blkno: u32,
+    pub growing_last_blkno: u32,
+    pub growing_full_page_count: u32,
+}
+
+impl MetaPageDataV2 {
+    const DOC_TERM_CNT: usize = 0;
+    const SEALED_TERM_INFO_BLKNO: usize = 8;
+    const SEALED_TERM_ID_CNT: usize = 12;
+    const VERSION: usize = 16;
+    const DOC_CNT: usize = 20;
+    const TERM_ID_CNT: usize = 24;
+    const SEALED_DOC_ID: usize = 28;
+    const CURRENT_DOC_ID: usize = 32;
+    const FIELD_NORM_BLKNO: usize = 36;
+    const PAYLOAD_BLKNO: usize = 40;
+    const TERM_STAT_BLKNO: usize = 44;
+    const DELETE_BITMAP_BLKNO: usize = 48;
+    const GROWING_FIRST_BLKNO: usize = 52;
+    const GROWING_LAST_BLKNO: usize = 56;
+    const GROWING_FULL_PAGE_COUNT: usize = 60;
+
+    pub fn decode(page: &PageData) -> Self {
+        let u32_at = |offset| read_u32(page, offset);
+        Self {
+            doc_term_cnt: read_u64(page, Self::DOC_TERM_CNT),
+            sealed_term_info_blkno: u32_at(Self::SEALED_TERM_INFO_BLKNO),
+            sealed_term_id_cnt: u32_at(Self::SEALED_TERM_ID_CNT),
+            version: u32_at(Self::VERSION),
+            doc_cnt: u32_at(Self::DOC_CNT),
+            term_id_cnt: u32_at(Self::TERM_ID_CNT),
+            sealed_doc_id: u32_at(Self::SEALED_DOC_ID),
+            current_doc_id: u32_at(Self::CURRENT_DOC_ID),
+            field_norm_blkno: u32_at(Self::FIELD_NORM_BLKNO),
+            payload_blkno: u32_at(Self::PAYLOAD_BLKNO),
+            term_stat_blkno: u32_at(Self::TERM_STAT_BLKNO),
+            delete_bitmap_blkno: u32_at(Self::DELETE_BITMAP_BLKNO),
+            growing_first_blkno: u32_at(Self::GROWING_FIRST_BLKNO),
+            growing_last_blkno: u32_at(Self::GROWING_LAST_BLKNO),
+            growing_full_page_count: u32_at(Self::GROWING_FULL_PAGE_COUNT),
+        }
+    }
+
+    pub fn read_version(page: &PageData) -> u32 {
+        read_u32(page, Self::VERSION)
+    }
+
+    pub fn write_version(page: &mut PageData, version: u32) {
+        page.content[Self::VERSION..Self::VERSION + 4].copy_from_slice(&version.to_ne_bytes());
+    }
+
+    // the index isn't bound to a tokenizer, it's taken from the index options
+    pub fn upgrade(&self) -> MetaPageData {
+        MetaPageData {
+            magic: META_MAGIC,
+            version: 3,
+            doc_cnt: self.doc_cnt,
+            doc_term_cnt: self.doc_term_cnt,
+            term_id_cnt: self.term_id_cnt,
+            sealed_doc_id: self.sealed_doc_id,
+            current_doc_id: self.current_doc_id,
+            field_norm_blkno: self.field_norm_blkno,
+            payload_blkno: self.payload_blkno,
+            term_stat_blkno: self.term_stat_blkno,
+            delete_bitmap_blkno: self.delete_bitmap_blkno,
+            growing_segment: std::num::NonZero::new(self.growing_first_blkno).map(|first_blkno| {
+                GrowingSegmentData {
+                    first_blkno,
+                    last_blkno: self.growing_last_blkno,
+                    growing_full_page_count: self.growing_full_page_count,
+                }
+            }),
+            sealed_segment: SealedSegmentData {
+                term_info_blkno: self.sealed_term_info_blkno,
+                term_id_cnt: self.sealed_term_id_cnt,
+            },
+            tokenizer: TokenizerTag::NONE,
+        }
+    }
+}
+
+fn read_u32(page: &PageData, offset: usize) -> u32 {
+    u32::from_ne_bytes(page.content[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(page: &PageData, offset: usize) -> u64 {
+    u64::from_ne_bytes(page.content[offset..offset + 8].try_into().unwrap())
+}
+
+// The on-disk format version of the metapage. A metapage of version 1 or 2 starts with
+// `doc_term_cnt`, it's only taken for a header if `doc_term_cnt` happens to be the magic
+// followed by a version, which needs more than 12 billion terms.
+pub fn meta_version(page: &PageData) -> u32 {
+    let header: &MetaPageHeader = page.as_ref();
+    if header.magic == META_MAGIC {
+        return header.version;
+    }
+    MetaPageDataV2::read_version(page)
+}
+
+// the name and the version of a tokenizer, the name is empty if the index is not bound to one
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TokenizerTag {
+    name: [u8; pgrx::pg_sys::NAMEDATALEN as usize],
+    pub version: u32,
+}
+
+impl TokenizerTag {
+    pub const NONE: Self = Self {
+        name: [0; pgrx::pg_sys::NAMEDATALEN as usize],
+        version: 0,
+    };
+
+    pub fn new(name: &str, version: u32) -> Self {
+        let mut tag = Self::NONE;
+        // tokenizer names are shorter than NAMEDATALEN, see `validate_tokenizer_name`
+        assert!(name.len() < tag.name.len());
+        tag.name[..name.len()].copy_from_slice(name.as_bytes());
+        tag.version = version;
+        tag
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.name.len());
+        (len > 0).then(|| std::str::from_utf8(&self.name[..len]).expect("invalid tokenizer name"))
+    }
 }
 
 impl MetaPageData {
@@ -23,3 +197,138 @@ impl MetaPageData {
         self.doc_term_cnt as f32 / self.doc_cnt as f32
     }
 }
+
+pub fn metapage_read(index: pgrx::pg_sys::Relation) -> PageReadGuard {
+    let page = page_read(index, METAPAGE_BLKNO);
+    check_version(index, &page);
+    page
+}
+
+pub fn metapage_write(index: pgrx::pg_sys::Relation) -> PageWriteGuard {
+    let page = page_write(index, METAPAGE_BLKNO);
+    check_version(index, &page);
+    page
+}
+
+fn check_version(index: pgrx::pg_sys::Relation, page: &PageData) {
+    let version = meta_version(page);
+    if version == META_VERSION {
+        return;
+    }
+    let index = unsafe { pgrx::PgRelation::from_pg(index) };
+    if version > META_VERSION {
+        pgrx::error!(
+            "bm25 index \"{}\" has on-disk format version {}, which is newer than the supported version {}",
+            index.name(),
+            version,
+            META_VERSION
+        );
+    }
+    pgrx::error!(
+        "bm25 index \"{}\" has on-disk format version {}, but version {} is required, run `SELECT bm25_upgrade_index('{}')` to upgrade it",
+        index.name(),
+        version,
+        META_VERSION,
+        index.name()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `MetaPageData` as it was declared in versions 1 and 2, with the default representation
+    #[allow(dead_code)]
+    struct MetaPageDataRust {
+        version: u32,
+        doc_cnt: u32,
+        doc_term_cnt: u64,
+        term_id_cnt: u32,
+        sealed_doc_id: u32,
+        current_doc_id: u32,
+        field_norm_blkno: u32,
+        payload_blkno: u32,
+        term_stat_blkno: u32,
+        delete_bitmap_blkno: u32,
+        growing_segment: Option<GrowingSegmentData>,
+        sealed_segment: SealedSegmentData,
+    }
+
+    fn zeroed_page() -> Box<PageData> {
+        unsafe { Box::new_zeroed().assume_init() }
+    }
+
+    #[test]
+    fn test_read_v2_metapage() {
+        let mut page = zeroed_page();
+        let ptr = page.content.as_mut_ptr().cast::<MetaPageDataRust>();
+        unsafe {
+            ptr.write(MetaPageDataRust {
+                version: 2,
+                doc_cnt: 3,
+                doc_term_cnt: 208,
+                term_id_cnt: 4,
+                sealed_doc_id: 1,
+                current_doc_id: 5,
+                field_norm_blkno: 6,
+                payload_blkno: 7,
+                term_stat_blkno: 8,
+                delete_bitmap_blkno: 9,
+                growing_segment: Some(GrowingSegmentData {
+                    first_blkno: std::num::NonZero::new(10).unwrap(),
+                    last_blkno: 11,
+                    growing_full_page_count: 12,
+                }),
+                sealed_segment: SealedSegmentData {
+                    term_info_blkno: 13,
+                    term_id_cnt: 14,
+                },
+            })
+        };
+        assert_eq!(meta_version(&page), 2);
+
+        let meta = MetaPageDataV2::decode(&page).upgrade();
+        assert_eq!(meta.version, 3);
+        assert_eq!(meta.doc_cnt, 3);
+        assert_eq!(meta.doc_term_cnt, 208);
+        assert_eq!(meta.term_id_cnt, 4);
+        assert_eq!(meta.sealed_doc_id, 1);
+        assert_eq!(meta.current_doc_id, 5);
+        assert_eq!(meta.field_norm_blkno, 6);
+        assert_eq!(meta.payload_blkno, 7);
+        assert_eq!(meta.term_stat_blkno, 8);
+        assert_eq!(meta.delete_bitmap_blkno, 9);
+        let growing = meta.growing_segment.unwrap();
+        assert_eq!(growing.first_blkno.get(), 10);
+        assert_eq!(growing.last_blkno, 11);
+        assert_eq!(growing.growing_full_page_count, 12);
+        assert_eq!(meta.sealed_segment.term_info_blkno, 13);
+        assert_eq!(meta.sealed_segment.term_id_cnt, 14);
+        assert_eq!(meta.tokenizer.name(), None);
+
+        let ptr = page.content.as_mut_ptr().cast::<MetaPageDataRust>();
+        unsafe { (*ptr).growing_segment = None };
+        assert!(MetaPageDataV2::decode(&page)
+            .upgrade()
+            .growing_segment
+            .is_none());
+
+        MetaPageDataV2::write_version(&mut page, 1);
+        assert_eq!(meta_version(&page), 1);
+        assert_eq!(MetaPageDataV2::decode(&page).doc_term_cnt, 208);
+    }
+
+    #[test]
+    fn test_read_v3_metapage() {
+        let mut page = zeroed_page();
+        let legacy = MetaPageDataV2::decode(&page);
+        let meta: &mut MetaPageData = (*page).as_mut();
+        *meta = MetaPageData {
+            tokenizer: TokenizerTag::new("Bert", 1),
+            ..legacy.upgrade()
+        };
+        assert_eq!(meta_version(&page), 3);
+        let meta: &MetaPageData = (*page).as_ref();
+        assert_eq!(meta.tokenizer.name(), Some("Bert"));
+    }
+}
@@ -73,4 +73,25 @@ impl SkipBlock {
             ((self.docid_bits as usize) << 8) | (self.tf_bits as usize)
         }
     }
+
+    pub fn last_doc(&self) -> u32 {
+        self.last_doc
+    }
+
+    pub fn blockwand_tf(&self) -> u32 {
+        self.blockwand_tf
+    }
+
+    pub fn blockwand_fieldnorm_id(&self) -> u8 {
+        self.blockwand_fieldnorm_id
+    }
+
+    // bit widths are only meaningful for fulled block
+    pub fn bits(&self) -> Option<(u8, u8)> {
+        if self.flag.contains(SkipBlockFlags::UNFULLED) {
+            None
+        } else {
+            Some((self.docid_bits, self.tf_bits))
+        }
+    }
 }
@@ -215,6 +215,11 @@ impl<const WITH_FREQ: bool> PostingReader<WITH_FREQ> {
         bm25_weight.score(fieldnorm, tf)
     }
 
+    pub fn skip_block(&self) -> &SkipBlock {
+        debug_assert!(!self.completed());
+        &self.skip_blocks[self.cur_block]
+    }
+
     pub fn last_doc_in_block(&self) -> u32 {
         if self.completed() {
             return TERMINATED_DOC;
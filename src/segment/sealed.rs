@@ -7,6 +7,7 @@ use super::{
     },
 };
 
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct SealedSegmentData {
     pub term_info_blkno: u32,
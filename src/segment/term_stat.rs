@@ -5,6 +5,10 @@ use crate::page::{VirtualPageReader, VirtualPageWriter};
 
 use super::meta::MetaPageData;
 
+pub trait TermStatRead {
+    fn read(&self, term_id: u32) -> u32;
+}
+
 pub struct TermStatReader {
     page_reader: VirtualPageReader,
     term_id_cnt: u32,
@@ -19,7 +23,19 @@ impl TermStatReader {
         }
     }
 
-    pub fn read(&self, term_id: u32) -> u32 {
+    pub fn update(&self, term_id: u32, f: impl FnOnce(&mut u32)) {
+        self.page_reader.update_at(
+            term_id * std::mem::size_of::<u32>() as u32,
+            std::mem::size_of::<u32>() as u32,
+            |data| {
+                f(bytemuck::from_bytes_mut(data));
+            },
+        );
+    }
+}
+
+impl TermStatRead for TermStatReader {
+    fn read(&self, term_id: u32) -> u32 {
         if term_id >= self.term_id_cnt {
             return 0;
         }
@@ -31,16 +47,6 @@ impl TermStatReader {
         );
         res
     }
-
-    pub fn update(&self, term_id: u32, f: impl FnOnce(&mut u32)) {
-        self.page_reader.update_at(
-            term_id * std::mem::size_of::<u32>() as u32,
-            std::mem::size_of::<u32>() as u32,
-            |data| {
-                f(bytemuck::from_bytes_mut(data));
-            },
-        );
-    }
 }
 
 pub fn extend_term_id(index: pgrx::pg_sys::Relation, meta: &mut MetaPageData, term_id_cnt: u32) {
@@ -3,19 +3,46 @@ CREATE TYPE bm25vector (
     OUTPUT = _bm25catalog_bm25vector_out,
     RECEIVE = _bm25catalog_bm25vector_recv,
     SEND = _bm25catalog_bm25vector_send,
-    STORAGE = EXTERNAL,
+    STORAGE = EXTENDED,
     INTERNALLENGTH = VARIABLE,
     ALIGNMENT = double
 );
 
 CREATE TYPE bm25query AS (
     index_oid regclass,
-    query_vector bm25vector
+    query_vector bm25vector,
+    after_rank real,
+    after_ctid tid,
+    tokenizer text,
+    after_tableoid oid
 );
 
 CREATE FUNCTION to_bm25query(index_oid regclass, query_str text, tokenizer_name text) RETURNS bm25query
     STABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
-        SELECT index_oid, tokenize(query_str, tokenizer_name);
+        SELECT index_oid, tokenize(query_str, tokenizer_name), NULL::real, NULL::tid, tokenizer_name, NULL::oid;
+    $$;
+
+-- the tokenizer is the one the index is built with, or the one in its options
+CREATE FUNCTION to_bm25query(index_oid regclass, query_str text) RETURNS bm25query
+    STABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT to_bm25query(index_oid, query_str, bm25_index_tokenizer(index_oid));
+    $$;
+
+-- a query of a vector tokenized beforehand, it has no tokenizer, so an index with one rejects it
+CREATE FUNCTION to_bm25query(index_oid regclass, query_vector bm25vector) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT index_oid, query_vector, NULL::real, NULL::tid, NULL::text, NULL::oid;
+    $$;
+
+CREATE FUNCTION bm25query_search_after(query bm25query, rank real, ctid tid) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT (query).index_oid, (query).query_vector, rank, ctid, (query).tokenizer, NULL::oid;
+    $$;
+
+-- the ties are ordered by table and ctid, to page through the partitions of a partitioned index
+CREATE FUNCTION bm25query_search_after(query bm25query, rank real, ctid tid, tableoid oid) RETURNS bm25query
+    IMMUTABLE STRICT PARALLEL SAFE LANGUAGE sql AS $$
+        SELECT (query).index_oid, (query).query_vector, rank, ctid, (query).tokenizer, tableoid;
     $$;
 
 CREATE ACCESS METHOD bm25 TYPE INDEX HANDLER _bm25_amhandler;
@@ -27,7 +54,28 @@ CREATE OPERATOR pg_catalog.<&> (
     RIGHTARG = bm25query
 );
 
+CREATE OPERATOR pg_catalog.<&> (
+    PROCEDURE = search_bm25query_text,
+    LEFTARG = text,
+    RIGHTARG = bm25query
+);
+
+CREATE OPERATOR pg_catalog.+ (
+    PROCEDURE = _bm25catalog_bm25vector_add,
+    LEFTARG = bm25vector,
+    RIGHTARG = bm25vector,
+    COMMUTATOR = +
+);
+
+CREATE CAST (bm25vector AS jsonb) WITH FUNCTION _bm25catalog_bm25vector_to_jsonb(bm25vector);
+CREATE CAST (jsonb AS bm25vector) WITH FUNCTION _bm25catalog_jsonb_to_bm25vector(jsonb);
+
 CREATE OPERATOR FAMILY bm25_ops USING bm25;
 
 CREATE OPERATOR CLASS bm25_ops FOR TYPE bm25vector USING bm25 FAMILY bm25_ops AS
     OPERATOR 1 pg_catalog.<&>(bm25vector, bm25query) FOR ORDER BY float_ops;
+
+CREATE OPERATOR FAMILY text_bm25_ops USING bm25;
+
+CREATE OPERATOR CLASS text_bm25_ops FOR TYPE text USING bm25 FAMILY text_bm25_ops AS
+    OPERATOR 1 pg_catalog.<&>(text, bm25query) FOR ORDER BY float_ops;
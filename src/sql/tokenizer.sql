@@ -1,6 +1,22 @@
 CREATE TABLE bm25_catalog.tokenizers (
     name TEXT NOT NULL UNIQUE PRIMARY KEY,
-    config TEXT NOT NULL
+    config TEXT NOT NULL,
+    -- the version pinned by the immutable `tokenize`, resolved by `create_tokenizer`
+    version INTEGER
+);
+
+-- the configs that tokenizers have been created with, kept after they're dropped, so that
+-- a name and a version always mean the same config
+CREATE TABLE bm25_catalog.tokenizer_versions (
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    fingerprint TEXT NOT NULL,
+    PRIMARY KEY (name, version)
+);
+
+CREATE TABLE bm25_catalog.huggingface_tokenizers (
+    name TEXT NOT NULL UNIQUE PRIMARY KEY REFERENCES bm25_catalog.tokenizers (name) ON DELETE CASCADE,
+    definition TEXT NOT NULL
 );
 
 CREATE FUNCTION unicode_tokenizer_insert_trigger()
@@ -11,7 +27,7 @@ DECLARE
 BEGIN
     EXECUTE format('
     WITH new_tokens AS (
-        SELECT unnest(unicode_tokenizer_split($1.%I)) AS token
+        SELECT unnest(unicode_tokenizer_split($1.%I, %L)) AS token
     ),
     to_insert AS (
         SELECT token FROM new_tokens
@@ -19,7 +35,7 @@ BEGIN
             SELECT 1 FROM bm25_catalog.%I WHERE token = new_tokens.token
         )
     )
-    INSERT INTO bm25_catalog.%I (token) SELECT token FROM to_insert ON CONFLICT (token) DO NOTHING', target_column, tokenizer_name, tokenizer_name) USING NEW;
+    INSERT INTO bm25_catalog.%I (token) SELECT token FROM to_insert ON CONFLICT (token) DO NOTHING', target_column, tokenizer_name, tokenizer_name, tokenizer_name) USING NEW;
     RETURN NEW;
 END;
 $$ LANGUAGE plpgsql;
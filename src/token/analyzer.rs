@@ -0,0 +1,298 @@
+/// The analyzer splits text into tokens with a configurable pipeline:
+/// character filters rewrite the text, the pre-tokenizer splits it into tokens,
+/// and token filters rewrite or remove each token in order.
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{language::Language, STOP_WORDS_LUCENE, STOP_WORDS_NLTK};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyzerConfig {
+    #[serde(default)]
+    pub char_filters: Vec<CharFilter>,
+    #[serde(default)]
+    pub pre_tokenizer: PreTokenizer,
+    #[serde(default)]
+    pub token_filters: Vec<TokenFilter>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum CharFilter {
+    // replace all matches of a regex
+    Replace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PreTokenizer {
+    // words by the Unicode word boundaries
+    #[default]
+    UnicodeWords,
+    Whitespace,
+    // every match of a regex is a token
+    Regex {
+        pattern: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TokenFilter {
+    Lowercase,
+    AsciiFolding,
+    // trim the trailing `'s`
+    EnglishPossessive,
+    // without `list`, `language` and `words`, the list of the tokenizer language is used
+    Stopwords {
+        #[serde(default)]
+        list: Option<StopwordList>,
+        #[serde(default)]
+        language: Option<Language>,
+        #[serde(default)]
+        words: Vec<String>,
+    },
+    // without `algorithm` and `language`, the stemmer of the tokenizer language is used
+    Stemmer {
+        #[serde(default)]
+        algorithm: Option<StemmerAlgorithm>,
+        #[serde(default)]
+        language: Option<Language>,
+    },
+    // the length is counted in characters
+    Length {
+        #[serde(default)]
+        min: Option<usize>,
+        #[serde(default)]
+        max: Option<usize>,
+    },
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopwordList {
+    Lucene,
+    Nltk,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StemmerAlgorithm {
+    Porter,
+    Porter2,
+}
+
+enum CompiledCharFilter {
+    Replace(regex::Regex, String),
+}
+
+enum CompiledPreTokenizer {
+    UnicodeWords,
+    Whitespace,
+    Regex(regex::Regex),
+}
+
+enum CompiledTokenFilter {
+    Lowercase,
+    AsciiFolding,
+    EnglishPossessive,
+    Stopwords(HashSet<String>),
+    Stemmer(Stemmer),
+    Length(usize, usize),
+}
+
+enum Stemmer {
+    Algorithm(StemmerAlgorithm),
+    Language(Language),
+}
+
+pub struct Analyzer {
+    char_filters: Vec<CompiledCharFilter>,
+    pre_tokenizer: CompiledPreTokenizer,
+    token_filters: Vec<CompiledTokenFilter>,
+}
+
+fn compile_regex(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern).map_err(|e| format!("invalid regex \"{}\": {}", pattern, e))
+}
+
+impl Analyzer {
+    pub fn new(config: &AnalyzerConfig, language: Language) -> Result<Self, String> {
+        let char_filters = config
+            .char_filters
+            .iter()
+            .map(|filter| match filter {
+                CharFilter::Replace {
+                    pattern,
+                    replacement,
+                } => Ok(CompiledCharFilter::Replace(
+                    compile_regex(pattern)?,
+                    replacement.clone(),
+                )),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let pre_tokenizer = match &config.pre_tokenizer {
+            PreTokenizer::UnicodeWords => CompiledPreTokenizer::UnicodeWords,
+            PreTokenizer::Whitespace => CompiledPreTokenizer::Whitespace,
+            PreTokenizer::Regex { pattern } => CompiledPreTokenizer::Regex(compile_regex(pattern)?),
+        };
+        let token_filters = config
+            .token_filters
+            .iter()
+            .map(|filter| match filter {
+                TokenFilter::Lowercase => Ok(CompiledTokenFilter::Lowercase),
+                TokenFilter::AsciiFolding => Ok(CompiledTokenFilter::AsciiFolding),
+                TokenFilter::EnglishPossessive => Ok(CompiledTokenFilter::EnglishPossessive),
+                TokenFilter::Stopwords {
+                    list,
+                    language: stopwords_language,
+                    words,
+                } => {
+                    let mut set = match list {
+                        Some(StopwordList::Lucene) => STOP_WORDS_LUCENE.clone(),
+                        Some(StopwordList::Nltk) => STOP_WORDS_NLTK.clone(),
+                        None => HashSet::new(),
+                    };
+                    match stopwords_language {
+                        Some(l) => set.extend(l.stopwords().iter().cloned()),
+                        None if list.is_none() && words.is_empty() => {
+                            set.extend(language.stopwords().iter().cloned())
+                        }
+                        None => {}
+                    }
+                    set.extend(words.iter().cloned());
+                    Ok(CompiledTokenFilter::Stopwords(set))
+                }
+                TokenFilter::Stemmer {
+                    algorithm,
+                    language: stemmer_language,
+                } => match (algorithm, stemmer_language) {
+                    (Some(_), Some(_)) => {
+                        Err("stemmer filter accepts only one of algorithm and language".to_string())
+                    }
+                    (Some(algorithm), None) => {
+                        Ok(CompiledTokenFilter::Stemmer(Stemmer::Algorithm(*algorithm)))
+                    }
+                    (None, l) => Ok(CompiledTokenFilter::Stemmer(Stemmer::Language(
+                        l.unwrap_or(language),
+                    ))),
+                },
+                TokenFilter::Length { min, max } => {
+                    let (min, max) = (min.unwrap_or(0), max.unwrap_or(usize::MAX));
+                    if min > max {
+                        return Err(format!(
+                            "length filter has min {} greater than max {}",
+                            min, max
+                        ));
+                    }
+                    Ok(CompiledTokenFilter::Length(min, max))
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            char_filters,
+            pre_tokenizer,
+            token_filters,
+        })
+    }
+
+    pub fn analyze(&self, text: &str) -> Vec<String> {
+        let mut text = std::borrow::Cow::Borrowed(text);
+        for filter in &self.char_filters {
+            match filter {
+                CompiledCharFilter::Replace(re, replacement) => {
+                    if let std::borrow::Cow::Owned(replaced) =
+                        re.replace_all(&text, replacement.as_str())
+                    {
+                        text = std::borrow::Cow::Owned(replaced);
+                    }
+                }
+            }
+        }
+        let words: Vec<&str> = match &self.pre_tokenizer {
+            CompiledPreTokenizer::UnicodeWords => text.unicode_words().collect(),
+            CompiledPreTokenizer::Whitespace => text.split_whitespace().collect(),
+            CompiledPreTokenizer::Regex(re) => re.find_iter(&text).map(|m| m.as_str()).collect(),
+        };
+        words
+            .into_iter()
+            .filter_map(|word| self.filter_token(word.to_string()))
+            .collect()
+    }
+
+    fn filter_token(&self, mut token: String) -> Option<String> {
+        for filter in &self.token_filters {
+            token = match filter {
+                CompiledTokenFilter::Lowercase => token.to_lowercase(),
+                CompiledTokenFilter::AsciiFolding => ascii_folding(&token),
+                CompiledTokenFilter::EnglishPossessive => trim_possessive(token),
+                CompiledTokenFilter::Stopwords(set) => {
+                    if set.contains(&token) {
+                        return None;
+                    }
+                    token
+                }
+                CompiledTokenFilter::Stemmer(stemmer) => match stemmer {
+                    Stemmer::Algorithm(StemmerAlgorithm::Porter) => {
+                        tantivy_stemmers::algorithms::english_porter(&token).to_string()
+                    }
+                    Stemmer::Algorithm(StemmerAlgorithm::Porter2) => {
+                        tantivy_stemmers::algorithms::english_porter_2(&token).to_string()
+                    }
+                    Stemmer::Language(language) => language.stem(&token).to_string(),
+                },
+                CompiledTokenFilter::Length(min, max) => {
+                    let len = token.chars().count();
+                    if len < *min || len > *max {
+                        return None;
+                    }
+                    token
+                }
+            };
+        }
+        (!token.is_empty()).then_some(token)
+    }
+}
+
+pub fn trim_possessive(token: String) -> String {
+    let chars = token.chars().collect::<Vec<char>>();
+    if chars.len() >= 2 && matches!(chars[chars.len() - 1], 's' | 'S') {
+        let c = chars[chars.len() - 2];
+        if c == '\'' || c == '\u{2019}' || c == '\u{FF07}' {
+            return chars[..chars.len() - 2].iter().collect();
+        }
+    }
+    token
+}
+
+// decompose the characters and drop the combining marks, e.g. `é` becomes `e`
+fn ascii_folding(token: &str) -> String {
+    let mut result = String::with_capacity(token.len());
+    for c in token.nfkd().filter(|&c| !is_combining_mark(c)) {
+        match c {
+            'ß' => result.push_str("ss"),
+            'æ' => result.push_str("ae"),
+            'Æ' => result.push_str("AE"),
+            'œ' => result.push_str("oe"),
+            'Œ' => result.push_str("OE"),
+            'ø' => result.push('o'),
+            'Ø' => result.push('O'),
+            'đ' => result.push('d'),
+            'Đ' => result.push('D'),
+            'ł' => result.push('l'),
+            'Ł' => result.push('L'),
+            'þ' => result.push_str("th"),
+            'Þ' => result.push_str("TH"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
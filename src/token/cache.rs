@@ -0,0 +1,327 @@
+// The backend-local cache of custom tokenizers, so that `tokenize` doesn't read the catalog
+// with SPI for every call.
+//
+// 1. A trigger on the tokenizer catalog invalidates the relcache entry of
+//    `bm25_catalog.tokenizers` on any change, and the callback drops the whole cache in every
+//    backend.
+// 2. The vocabulary of a trained tokenizer only grows, so a token missing in the cache is
+//    looked up in the vocabulary table and then cached. A token missing in the vocabulary is
+//    cached as missing too, so that it's not looked up again for every call.
+// 3. A trigger on the vocabulary table invalidates its relcache entry on an insert, and the
+//    callback drops the missing tokens of every tokenizer. Any other change of the vocabulary
+//    drops the whole cache like a change of the catalog.
+// 4. The tokens inserted by an aborted transaction are rolled back, so the cache is dropped
+//    if the vocabulary is read in an aborted transaction or subtransaction.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use pgrx::{
+    pg_sys::{self, panic::ErrorReportable},
+    IntoDatum,
+};
+
+use crate::utils::cells::{PgCell, PgRefCell};
+
+use super::{analyzer::Analyzer, huggingface, read_config, TokenizerConfig, TokenizerKind};
+
+type RelcacheCallbackFunction = unsafe extern "C" fn(arg: pg_sys::Datum, relid: pg_sys::Oid);
+
+// `utils/inval.h` is not in the bindings of pgrx, so the calls are guarded by `pg_guard_ffi_boundary`
+extern "C" {
+    fn CacheRegisterRelcacheCallback(func: RelcacheCallbackFunction, arg: pg_sys::Datum);
+    fn CacheInvalidateRelcacheByRelid(relid: pg_sys::Oid);
+}
+
+pub struct Tokenizer {
+    pub config: TokenizerConfig,
+    pub analyzer: Option<Analyzer>,
+    pub huggingface: Option<tokenizers::Tokenizer>,
+    vocab: HashMap<String, u32>,
+    // the tokens not in the vocabulary, as of `missing_generation`
+    missing: HashSet<String>,
+    missing_generation: u64,
+}
+
+struct Cache {
+    generation: u64,
+    tokenizers: BTreeMap<String, Tokenizer>,
+}
+
+static CACHE: PgRefCell<Cache> = unsafe {
+    PgRefCell::new(Cache {
+        generation: 0,
+        tokenizers: BTreeMap::new(),
+    })
+};
+// bumped to drop the cache, the callbacks don't touch `CACHE` as it may be borrowed
+static GENERATION: PgCell<u64> = unsafe { PgCell::new(0) };
+static TOKENIZERS_RELID: PgCell<pg_sys::Oid> = unsafe { PgCell::new(pg_sys::Oid::INVALID) };
+// bumped to drop the missing tokens, when a token is inserted into one of `VOCAB_RELIDS`
+static MISSING_GENERATION: PgCell<u64> = unsafe { PgCell::new(0) };
+// the vocabulary tables of the cached tokenizers, it's never borrowed across a call to postgres
+static VOCAB_RELIDS: PgRefCell<Vec<pg_sys::Oid>> = unsafe { PgRefCell::new(Vec::new()) };
+// whether the vocabulary is read in the current transaction
+static VOCAB_READ: PgCell<bool> = unsafe { PgCell::new(false) };
+
+pub fn init() {
+    unsafe {
+        pg_sys::ffi::pg_guard_ffi_boundary(|| {
+            CacheRegisterRelcacheCallback(relcache_callback, pg_sys::Datum::from(0))
+        });
+        pg_sys::RegisterXactCallback(Some(xact_callback), std::ptr::null_mut());
+        pg_sys::RegisterSubXactCallback(Some(subxact_callback), std::ptr::null_mut());
+    }
+}
+
+unsafe extern "C" fn relcache_callback(_arg: pg_sys::Datum, relid: pg_sys::Oid) {
+    if relid == pg_sys::Oid::INVALID || VOCAB_RELIDS.borrow().contains(&relid) {
+        MISSING_GENERATION.set(MISSING_GENERATION.get() + 1);
+    }
+    let tokenizers_relid = TOKENIZERS_RELID.get();
+    if tokenizers_relid == pg_sys::Oid::INVALID {
+        return;
+    }
+    if relid == pg_sys::Oid::INVALID || relid == tokenizers_relid {
+        // the table is resolved again, in case the extension is dropped
+        TOKENIZERS_RELID.set(pg_sys::Oid::INVALID);
+        GENERATION.set(GENERATION.get() + 1);
+    }
+}
+
+unsafe extern "C" fn xact_callback(event: pg_sys::XactEvent::Type, _arg: *mut std::ffi::c_void) {
+    match event {
+        pg_sys::XactEvent::XACT_EVENT_ABORT | pg_sys::XactEvent::XACT_EVENT_PARALLEL_ABORT => {
+            if VOCAB_READ.get() {
+                GENERATION.set(GENERATION.get() + 1);
+            }
+            VOCAB_READ.set(false);
+        }
+        pg_sys::XactEvent::XACT_EVENT_COMMIT | pg_sys::XactEvent::XACT_EVENT_PARALLEL_COMMIT => {
+            VOCAB_READ.set(false);
+        }
+        _ => {}
+    }
+}
+
+unsafe extern "C" fn subxact_callback(
+    event: pg_sys::SubXactEvent::Type,
+    _my_subid: pg_sys::SubTransactionId,
+    _parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut std::ffi::c_void,
+) {
+    if event == pg_sys::SubXactEvent::SUBXACT_EVENT_ABORT_SUB && VOCAB_READ.get() {
+        GENERATION.set(GENERATION.get() + 1);
+    }
+}
+
+fn tokenizers_relid() -> pg_sys::Oid {
+    if TOKENIZERS_RELID.get() == pg_sys::Oid::INVALID {
+        let relid = unsafe {
+            let namespace = pg_sys::get_namespace_oid(c"bm25_catalog".as_ptr(), false);
+            pg_sys::get_relname_relid(c"tokenizers".as_ptr(), namespace)
+        };
+        TOKENIZERS_RELID.set(relid);
+    }
+    TOKENIZERS_RELID.get()
+}
+
+// drop the cache of all backends after the current command or transaction
+pub fn invalidate() {
+    let relid = tokenizers_relid();
+    unsafe { pg_sys::ffi::pg_guard_ffi_boundary(|| CacheInvalidateRelcacheByRelid(relid)) };
+}
+
+// drop the missing tokens of all backends after the current command or transaction
+pub fn invalidate_missing(vocab_relid: pg_sys::Oid) {
+    unsafe { pg_sys::ffi::pg_guard_ffi_boundary(|| CacheInvalidateRelcacheByRelid(vocab_relid)) };
+}
+
+// The tokenizer is taken out of the cache while `f` runs, since `f` may run SPI, which
+// may invalidate the cache.
+pub fn with_tokenizer<R>(tokenizer_name: &str, f: impl FnOnce(&mut Tokenizer) -> R) -> R {
+    let generation = GENERATION.get();
+    let cached = {
+        let mut cache = CACHE.borrow_mut();
+        if cache.generation != generation {
+            cache.tokenizers.clear();
+            cache.generation = generation;
+            VOCAB_RELIDS.borrow_mut().clear();
+        }
+        cache.tokenizers.remove(tokenizer_name)
+    };
+    let mut tokenizer = cached.unwrap_or_else(|| Tokenizer::load(tokenizer_name));
+    let result = f(&mut tokenizer);
+    let mut cache = CACHE.borrow_mut();
+    if cache.generation == GENERATION.get() {
+        cache
+            .tokenizers
+            .insert(tokenizer_name.to_string(), tokenizer);
+    }
+    result
+}
+
+impl Tokenizer {
+    fn load(tokenizer_name: &str) -> Self {
+        // resolve the table before reading it, so that its invalidation is not missed
+        tokenizers_relid();
+        pgrx::Spi::connect(|client| {
+            let config = read_config(&client, tokenizer_name);
+            let analyzer = config.analyzer();
+            let huggingface = matches!(config.tokenizer, TokenizerKind::HuggingFace)
+                .then(|| huggingface::load(&client, tokenizer_name));
+            if config.is_trained() {
+                let relid = vocab_relid(tokenizer_name);
+                VOCAB_RELIDS.borrow_mut().push(relid);
+            }
+            Self {
+                config,
+                analyzer,
+                huggingface,
+                vocab: HashMap::new(),
+                missing: HashSet::new(),
+                missing_generation: MISSING_GENERATION.get(),
+            }
+        })
+    }
+
+    // Map the tokens of a trained tokenizer to ids. Tokens not in the vocabulary are added
+    // to it if `grow` is set, otherwise they are skipped.
+    pub fn lookup(&mut self, tokenizer_name: &str, tokens: Vec<String>, grow: bool) -> Vec<u32> {
+        // the generation is read before the vocabulary, so that an insert during the lookup
+        // drops the missing tokens found by it
+        let missing_generation = MISSING_GENERATION.get();
+        if self.missing_generation != missing_generation {
+            self.missing.clear();
+            self.missing_generation = missing_generation;
+        }
+        let missing = tokens
+            .iter()
+            .filter(|token| {
+                !self.vocab.contains_key(*token) && (grow || !self.missing.contains(*token))
+            })
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        if !missing.is_empty() {
+            VOCAB_READ.set(true);
+            let missing = missing.into_iter().collect::<Vec<_>>();
+            let found = select_vocab(tokenizer_name, missing.clone());
+            self.vocab.extend(found);
+            let new = missing
+                .into_iter()
+                .filter(|token| !self.vocab.contains_key(token))
+                .collect::<Vec<_>>();
+            if grow && !new.is_empty() {
+                let inserted = insert_vocab(tokenizer_name, new);
+                self.vocab.extend(inserted);
+            } else {
+                self.missing.extend(new);
+            }
+        }
+        tokens
+            .into_iter()
+            .filter_map(|token| self.vocab.get(&token).copied())
+            .collect()
+    }
+}
+
+fn vocab_relid(tokenizer_name: &str) -> pg_sys::Oid {
+    let name = std::ffi::CString::new(tokenizer_name).unwrap();
+    unsafe {
+        let namespace = pg_sys::get_namespace_oid(c"bm25_catalog".as_ptr(), false);
+        pg_sys::get_relname_relid(name.as_ptr(), namespace)
+    }
+}
+
+fn select_vocab(tokenizer_name: &str, tokens: Vec<String>) -> Vec<(String, u32)> {
+    pgrx::Spi::connect(|client| {
+        let query = format!(
+            "SELECT id, token FROM bm25_catalog.\"{}\" WHERE token = ANY($1)",
+            tokenizer_name
+        );
+        let args = Some(vec![(
+            pgrx::PgBuiltInOids::TEXTARRAYOID.oid(),
+            tokens.into_datum(),
+        )]);
+        let rows = client.select(&query, None, args).unwrap_or_report();
+        read_vocab(rows)
+    })
+}
+
+// the vocabulary is not cached by id, since ids are only decoded for inspection
+pub fn select_tokens(tokenizer_name: &str, ids: &[u32]) -> HashMap<u32, String> {
+    pgrx::Spi::connect(|client| {
+        let query = format!(
+            "SELECT id, token FROM bm25_catalog.\"{}\" WHERE id = ANY($1)",
+            tokenizer_name
+        );
+        let ids = ids.iter().map(|&id| id as i32).collect::<Vec<_>>();
+        let args = Some(vec![(
+            pgrx::PgBuiltInOids::INT4ARRAYOID.oid(),
+            ids.into_datum(),
+        )]);
+        let rows = client.select(&query, None, args).unwrap_or_report();
+        read_vocab(rows)
+            .into_iter()
+            .map(|(token, id)| (id, token))
+            .collect()
+    })
+}
+
+// The tokens are inserted in order, so concurrent inserts don't deadlock. A token inserted
+// by a concurrent transaction is selected after its insert is skipped. It's invisible to the
+// snapshot of a `REPEATABLE READ` or `SERIALIZABLE` transaction, which then fails with a
+// serialization error to be retried, instead of dropping the token.
+fn insert_vocab(tokenizer_name: &str, tokens: Vec<String>) -> Vec<(String, u32)> {
+    let tokens_cnt = tokens.len();
+    pgrx::Spi::connect(|mut client| {
+        let query = format!(
+            r#"
+            INSERT INTO bm25_catalog."{}" (token) SELECT unnest($1::text[])
+            ON CONFLICT (token) DO NOTHING
+            RETURNING id, token
+            "#,
+            tokenizer_name
+        );
+        let args = Some(vec![(
+            pgrx::PgBuiltInOids::TEXTARRAYOID.oid(),
+            tokens.clone().into_datum(),
+        )]);
+        let rows = client.update(&query, None, args).unwrap_or_report();
+        let mut vocab = read_vocab(rows);
+        if vocab.len() < tokens.len() {
+            let query = format!(
+                "SELECT id, token FROM bm25_catalog.\"{}\" WHERE token = ANY($1)",
+                tokenizer_name
+            );
+            let args = Some(vec![(
+                pgrx::PgBuiltInOids::TEXTARRAYOID.oid(),
+                tokens.into_datum(),
+            )]);
+            let rows = client.update(&query, None, args).unwrap_or_report();
+            vocab = read_vocab(rows);
+        }
+        if vocab.len() < tokens_cnt {
+            pgrx::ereport!(
+                pgrx::PgLogLevel::ERROR,
+                pgrx::PgSqlErrorCode::ERRCODE_T_R_SERIALIZATION_FAILURE,
+                "could not serialize access due to concurrent update of the vocabulary",
+                format!(
+                    "The vocabulary of tokenizer \"{}\" is grown by a concurrent transaction.",
+                    tokenizer_name
+                )
+            );
+        }
+        vocab
+    })
+}
+
+fn read_vocab(rows: pgrx::spi::SpiTupleTable<'_>) -> Vec<(String, u32)> {
+    let mut vocab = Vec::new();
+    for row in rows {
+        let id: i32 = row.get(1).unwrap_or_report().expect("no id value");
+        let id = u32::try_from(id).expect("id is not a valid u32");
+        let token: String = row.get(2).unwrap_or_report().expect("no token value");
+        vocab.push((token, id));
+    }
+    vocab
+}
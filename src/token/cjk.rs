@@ -0,0 +1,59 @@
+// Chinese, Japanese and Korean text has no spaces between words, so a run of CJK
+// characters is split into overlapping character bigrams, e.g. `全文检索` becomes
+// `全文`, `文检` and `检索`. A single CJK character is kept as a unigram.
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        // Han
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2A6DF}'
+        | '\u{2A700}'..='\u{2EBEF}'
+        | '\u{2F800}'..='\u{2FA1F}'
+        | '\u{30000}'..='\u{3134F}'
+        // Hiragana and Katakana
+        | '\u{3040}'..='\u{309F}'
+        | '\u{30A0}'..='\u{30FF}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{FF66}'..='\u{FF9F}'
+        // Hangul
+        | '\u{1100}'..='\u{11FF}'
+        | '\u{3130}'..='\u{318F}'
+        | '\u{AC00}'..='\u{D7AF}'
+    )
+}
+
+fn push_bigrams(tokens: &mut Vec<String>, run: &[char]) {
+    if run.len() == 1 {
+        tokens.push(run[0].to_string());
+    }
+    for pair in run.windows(2) {
+        tokens.push(pair.iter().collect());
+    }
+}
+
+// CJK runs become bigrams, the text between them is split by `split_other`
+pub fn cjk_split(text: &str, mut split_other: impl FnMut(&str) -> Vec<String>) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut run = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if is_cjk(c) {
+            if run.is_empty() {
+                tokens.extend(split_other(&text[start..i]));
+            }
+            run.push(c);
+        } else if !run.is_empty() {
+            push_bigrams(&mut tokens, &run);
+            run.clear();
+            start = i;
+        }
+    }
+    if run.is_empty() {
+        tokens.extend(split_other(&text[start..]));
+    } else {
+        push_bigrams(&mut tokens, &run);
+    }
+    tokens
+}
@@ -0,0 +1,59 @@
+// HuggingFace tokenizers are loaded from `tokenizer.json` definitions stored in
+// `bm25_catalog.huggingface_tokenizers`.
+
+use std::str::FromStr;
+
+use pgrx::{pg_sys::panic::ErrorReportable, spi::SpiClient, IntoDatum};
+
+use super::analyzer::Analyzer;
+
+pub fn parse(definition: &str) -> tokenizers::Tokenizer {
+    tokenizers::Tokenizer::from_str(definition)
+        .unwrap_or_else(|e| panic!("Invalid HuggingFace tokenizer definition, Details: {}", e))
+}
+
+pub fn load(client: &SpiClient<'_>, tokenizer_name: &str) -> tokenizers::Tokenizer {
+    let query = "SELECT definition FROM bm25_catalog.huggingface_tokenizers WHERE name = $1";
+    let args = Some(vec![(
+        pgrx::PgBuiltInOids::TEXTOID.oid(),
+        tokenizer_name.into_datum(),
+    )]);
+    let mut rows = client.select(query, None, args).unwrap_or_report();
+    if rows.len() != 1 {
+        panic!("HuggingFace tokenizer definition not found");
+    }
+    let definition: &str = rows
+        .next()
+        .unwrap()
+        .get(1)
+        .unwrap_or_report()
+        .expect("no definition value");
+    parse(definition)
+}
+
+pub fn encode_tokens(
+    tokenizer: &tokenizers::Tokenizer,
+    tokens: impl IntoIterator<Item = String>,
+) -> Vec<u32> {
+    let mut results = Vec::new();
+    for token in tokens {
+        let encoding = tokenizer.encode_fast(token, false).unwrap();
+        results.extend_from_slice(encoding.get_ids());
+    }
+    results
+}
+
+pub fn encode(
+    tokenizer: &tokenizers::Tokenizer,
+    analyzer: Option<&Analyzer>,
+    text: &str,
+) -> Vec<u32> {
+    match analyzer {
+        Some(analyzer) => encode_tokens(tokenizer, analyzer.analyze(text)),
+        None => tokenizer
+            .encode_fast(text, false)
+            .unwrap()
+            .get_ids()
+            .to_vec(),
+    }
+}
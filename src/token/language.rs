@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use tantivy_stemmers::algorithms;
+
+// languages with both a stemmer in tantivy-stemmers and a stopword list in stop-words
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    Arabic,
+    Danish,
+    Dutch,
+    #[default]
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Turkish,
+}
+
+const LANGUAGES: [(Language, stop_words::LANGUAGE); 17] = [
+    (Language::Arabic, stop_words::LANGUAGE::Arabic),
+    (Language::Danish, stop_words::LANGUAGE::Danish),
+    (Language::Dutch, stop_words::LANGUAGE::Dutch),
+    (Language::English, stop_words::LANGUAGE::English),
+    (Language::Finnish, stop_words::LANGUAGE::Finnish),
+    (Language::French, stop_words::LANGUAGE::French),
+    (Language::German, stop_words::LANGUAGE::German),
+    (Language::Greek, stop_words::LANGUAGE::Greek),
+    (Language::Hungarian, stop_words::LANGUAGE::Hungarian),
+    (Language::Italian, stop_words::LANGUAGE::Italian),
+    (Language::Norwegian, stop_words::LANGUAGE::Norwegian),
+    (Language::Portuguese, stop_words::LANGUAGE::Portuguese),
+    (Language::Romanian, stop_words::LANGUAGE::Romanian),
+    (Language::Russian, stop_words::LANGUAGE::Russian),
+    (Language::Spanish, stop_words::LANGUAGE::Spanish),
+    (Language::Swedish, stop_words::LANGUAGE::Swedish),
+    (Language::Turkish, stop_words::LANGUAGE::Turkish),
+];
+
+lazy_static::lazy_static! {
+    static ref STOP_WORDS: HashMap<Language, HashSet<String>> = LANGUAGES
+        .into_iter()
+        .map(|(language, name)| (language, stop_words::get(name).into_iter().collect()))
+        .collect();
+}
+
+impl Language {
+    pub fn stopwords(self) -> &'static HashSet<String> {
+        &STOP_WORDS[&self]
+    }
+
+    // English uses Porter2
+    pub fn stem(self, token: &str) -> Cow<'_, str> {
+        match self {
+            Language::Arabic => algorithms::arabic(token),
+            Language::Danish => algorithms::danish(token),
+            Language::Dutch => algorithms::dutch(token),
+            Language::English => algorithms::english_porter_2(token),
+            Language::Finnish => algorithms::finnish(token),
+            Language::French => algorithms::french(token),
+            Language::German => algorithms::german(token),
+            Language::Greek => algorithms::greek(token),
+            Language::Hungarian => algorithms::hungarian(token),
+            Language::Italian => algorithms::italian(token),
+            Language::Norwegian => algorithms::norwegian_bokmal(token),
+            Language::Portuguese => algorithms::portuguese(token),
+            Language::Romanian => algorithms::romanian(token),
+            Language::Russian => algorithms::russian(token),
+            Language::Spanish => algorithms::spanish(token),
+            Language::Swedish => algorithms::swedish(token),
+            Language::Turkish => algorithms::turkish_cilden(token),
+        }
+    }
+}
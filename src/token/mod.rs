@@ -0,0 +1,898 @@
+mod analyzer;
+mod cache;
+mod cjk;
+mod huggingface;
+mod language;
+mod ngram;
+mod shingle;
+
+use std::collections::{HashMap, HashSet};
+
+use pgrx::{
+    extension_sql, extension_sql_file,
+    iter::TableIterator,
+    name,
+    pg_sys::panic::ErrorReportable,
+    pg_trigger,
+    prelude::{PgHeapTuple, PgTriggerOperation},
+    spi::SpiClient,
+    AllocatedByPostgres, IntoDatum, WhoAllocated,
+};
+use serde::{Deserialize, Serialize};
+use tocken::tokenizer::Tokenizer as Tockenizer;
+use unicode_segmentation::UnicodeSegmentation;
+use validator::{Validate, ValidationError};
+
+use crate::datatype::{Bm25VectorInput, Bm25VectorOutput};
+
+use analyzer::{trim_possessive, Analyzer, AnalyzerConfig};
+use language::Language;
+use shingle::ShingleConfig;
+
+pub fn init() {
+    cache::init();
+}
+
+static BERT_BASE_UNCASED_BYTES: &[u8] = include_bytes!("../../tokenizer/bert_base_uncased.json");
+static TOCKEN: &[u8] = include_bytes!("../../tokenizer/wiki_tocken.json");
+
+const TOKEN_PATTERN: &str = r"(?u)\b\w\w+\b";
+
+lazy_static::lazy_static! {
+    static ref TOKEN_PATTERN_RE: regex::Regex = regex::Regex::new(TOKEN_PATTERN).unwrap();
+    pub static ref STOP_WORDS_LUCENE: HashSet<String> = {
+        [
+            "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is",
+            "it", "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there",
+            "these", "they", "this", "to", "was", "will", "with",
+        ].iter().map(|s| s.to_string()).collect()
+    };
+    pub static ref STOP_WORDS_NLTK: HashSet<String> = {
+        let words = stop_words::get(stop_words::LANGUAGE::English);
+        words.into_iter().collect()
+    };
+
+    static ref BERT_TOKENIZER: BertWithStemmerAndSplit = BertWithStemmerAndSplit::new();
+    static ref TOCKENIZER: Tocken = Tocken::new();
+}
+
+struct BertWithStemmerAndSplit(tokenizers::Tokenizer);
+
+impl BertWithStemmerAndSplit {
+    fn new() -> Self {
+        Self(tokenizers::Tokenizer::from_bytes(BERT_BASE_UNCASED_BYTES).unwrap())
+    }
+
+    fn encode(&self, text: &str, language: Language) -> Vec<u32> {
+        let mut results = Vec::new();
+        let lower_text = text.to_lowercase();
+        let split = TOKEN_PATTERN_RE.find_iter(&lower_text);
+        for token in split {
+            if language.stopwords().contains(token.as_str()) {
+                continue;
+            }
+            let stemmed_token = language.stem(token.as_str()).to_string();
+            let encoding = self.0.encode_fast(stemmed_token, false).unwrap();
+            results.extend_from_slice(encoding.get_ids());
+        }
+        results
+    }
+
+    fn encode_with(&self, analyzer: &Analyzer, text: &str) -> Vec<u32> {
+        huggingface::encode_tokens(&self.0, analyzer.analyze(text))
+    }
+}
+
+struct Tocken(Tockenizer, HashMap<u32, String>);
+
+// the vocabulary of tocken is private, so it's read from the model for decoding
+#[derive(Deserialize)]
+struct TockenTable {
+    table: HashMap<String, u32>,
+}
+
+impl Tocken {
+    fn new() -> Self {
+        let model = std::str::from_utf8(TOCKEN).unwrap();
+        let table: TockenTable = serde_json::from_str(model).unwrap();
+        let tokens = table.table.into_iter().map(|(t, id)| (id, t)).collect();
+        Self(tocken::tokenizer::Tokenizer::loads(model), tokens)
+    }
+
+    fn encode(&self, text: &str) -> Vec<u32> {
+        self.0.tokenize(text)
+    }
+
+    fn decode(&self, id: u32) -> Option<String> {
+        self.1.get(&id).cloned()
+    }
+}
+
+#[pgrx::pg_extern(immutable, strict, parallel_safe)]
+pub fn unicode_tokenizer_split(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for (token, count) in english_words(text) {
+        for _ in 0..count {
+            tokens.push(token.clone());
+        }
+    }
+    tokens
+}
+
+// the stemmed words of `unicode_tokenizer_split`, a word is counted once for each of
+// the Lucene and NLTK stopword lists that doesn't contain it
+fn english_words(text: &str) -> impl Iterator<Item = (String, usize)> + '_ {
+    text.unicode_words().filter_map(|word| {
+        // trim `'s` for English
+        let lowercase = trim_possessive(word.to_lowercase());
+        let token = tantivy_stemmers::algorithms::english_porter(&lowercase).to_string();
+        if token.is_empty() {
+            return None;
+        }
+        let count = usize::from(!STOP_WORDS_LUCENE.contains(&lowercase))
+            + usize::from(!STOP_WORDS_NLTK.contains(&lowercase));
+        (count > 0).then_some((token, count))
+    })
+}
+
+// without the English specific rules of `unicode_tokenizer_split`
+fn unicode_split_language(text: &str, language: Language) -> Vec<String> {
+    let stopwords = language.stopwords();
+    let mut tokens = Vec::new();
+    for word in text.unicode_words() {
+        let lowercase = word.to_lowercase();
+        if stopwords.contains(&lowercase) {
+            continue;
+        }
+        let token = language.stem(&lowercase).to_string();
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+// split the text into the tokens of a trained tokenizer, it's used to build its vocabulary
+#[pgrx::pg_extern(name = "unicode_tokenizer_split", stable, strict, parallel_safe)]
+pub fn unicode_tokenizer_split_with(text: &str, tokenizer_name: &str) -> Vec<String> {
+    cache::with_tokenizer(tokenizer_name, |tokenizer| {
+        if !tokenizer.config.is_trained() {
+            panic!(
+                "Tokenizer {} is not a Unicode, Cjk or Ngram tokenizer",
+                tokenizer_name
+            );
+        }
+        tokenizer.config.split(tokenizer.analyzer.as_ref(), text)
+    })
+}
+
+fn unicode_split(analyzer: Option<&Analyzer>, language: Language, text: &str) -> Vec<String> {
+    match (analyzer, language) {
+        (Some(analyzer), _) => analyzer.analyze(text),
+        (None, Language::English) => unicode_tokenizer_split(text),
+        (None, language) => unicode_split_language(text, language),
+    }
+}
+
+// the tokens of `unicode_split` followed by the shingles of its words
+fn unicode_split_with_shingles(
+    analyzer: Option<&Analyzer>,
+    language: Language,
+    shingle: &ShingleConfig,
+    text: &str,
+) -> Vec<String> {
+    let (mut tokens, words) = match (analyzer, language) {
+        (None, Language::English) => {
+            let words = english_words(text).collect::<Vec<_>>();
+            let tokens = words
+                .iter()
+                .flat_map(|(token, count)| std::iter::repeat_n(token.clone(), *count))
+                .collect();
+            (tokens, words.into_iter().map(|(token, _)| token).collect())
+        }
+        _ => {
+            let words = unicode_split(analyzer, language, text);
+            (words.clone(), words)
+        }
+    };
+    tokens.extend(shingle.shingles(&words));
+    tokens
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[repr(i32)]
+enum TokenizerKind {
+    Bert,
+    Tocken,
+    Unicode,
+    Cjk,
+    Ngram,
+    HuggingFace,
+}
+
+#[derive(Clone, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "TokenizerConfig::validate_unicode"))]
+#[serde(deny_unknown_fields)]
+struct TokenizerConfig {
+    tokenizer: TokenizerKind,
+    #[serde(default)]
+    table: Option<String>,
+    #[serde(default)]
+    column: Option<String>,
+    #[serde(default)]
+    language: Option<Language>,
+    #[serde(default)]
+    min_gram: Option<usize>,
+    #[serde(default)]
+    max_gram: Option<usize>,
+    #[serde(default)]
+    shingle: Option<ShingleConfig>,
+    // assign ids to new tokens when documents are tokenized, instead of training on a table
+    #[serde(default)]
+    grow_vocabulary: bool,
+    // pinned by the immutable `tokenize`, it's resolved by `resolve_version` if not given
+    #[serde(default)]
+    version: Option<u32>,
+    #[serde(default)]
+    analyzer: Option<AnalyzerConfig>,
+}
+
+impl TokenizerConfig {
+    fn language(&self) -> Language {
+        self.language.unwrap_or_default()
+    }
+
+    fn version(&self) -> u32 {
+        self.version.unwrap_or(1)
+    }
+
+    fn analyzer(&self) -> Option<Analyzer> {
+        let config = self.analyzer.as_ref()?;
+        let analyzer = Analyzer::new(config, self.language())
+            .unwrap_or_else(|e| panic!("Invalid analyzer config, Details: {}", e));
+        Some(analyzer)
+    }
+
+    // the vocabulary of Unicode, Cjk and Ngram tokenizers is trained on the data
+    fn is_trained(&self) -> bool {
+        matches!(
+            self.tokenizer,
+            TokenizerKind::Unicode | TokenizerKind::Cjk | TokenizerKind::Ngram
+        )
+    }
+
+    fn gram_range(&self) -> (usize, usize) {
+        (
+            self.min_gram.unwrap_or(ngram::DEFAULT_MIN_GRAM),
+            self.max_gram.unwrap_or(ngram::DEFAULT_MAX_GRAM),
+        )
+    }
+
+    // split the text into the tokens of a trained tokenizer
+    fn split(&self, analyzer: Option<&Analyzer>, text: &str) -> Vec<String> {
+        let language = self.language();
+        match self.tokenizer {
+            TokenizerKind::Cjk => {
+                cjk::cjk_split(text, |other| unicode_split(analyzer, language, other))
+            }
+            TokenizerKind::Ngram => {
+                let words = match analyzer {
+                    Some(analyzer) => analyzer.analyze(text),
+                    None => ngram::words(text),
+                };
+                let (min_gram, max_gram) = self.gram_range();
+                ngram::ngram_split(words, min_gram, max_gram)
+            }
+            _ => match &self.shingle {
+                Some(shingle) => unicode_split_with_shingles(analyzer, language, shingle, text),
+                None => unicode_split(analyzer, language, text),
+            },
+        }
+    }
+
+    fn validate_unicode(&self) -> Result<(), ValidationError> {
+        if matches!(self.version, Some(version) if version == 0 || version > i32::MAX as u32) {
+            return Err(ValidationError::new("version must be positive"));
+        }
+        if matches!(self.tokenizer, TokenizerKind::Tocken) && self.analyzer.is_some() {
+            return Err(ValidationError::new(
+                "analyzer is not supported for tocken tokenizer",
+            ));
+        }
+        if matches!(self.tokenizer, TokenizerKind::Tocken) && self.language.is_some() {
+            return Err(ValidationError::new(
+                "language is not supported for tocken tokenizer",
+            ));
+        }
+        if matches!(self.tokenizer, TokenizerKind::HuggingFace)
+            && self.language.is_some()
+            && self.analyzer.is_none()
+        {
+            return Err(ValidationError::new(
+                "language requires an analyzer for huggingface tokenizer",
+            ));
+        }
+        if matches!(self.tokenizer, TokenizerKind::Ngram) {
+            let (min_gram, max_gram) = self.gram_range();
+            if min_gram == 0 {
+                return Err(ValidationError::new("min_gram must be positive"));
+            }
+            if min_gram > max_gram {
+                return Err(ValidationError::new(
+                    "min_gram must not be greater than max_gram",
+                ));
+            }
+        } else if self.min_gram.is_some() || self.max_gram.is_some() {
+            return Err(ValidationError::new(
+                "min_gram and max_gram are only supported for ngram tokenizer",
+            ));
+        }
+        if let Some(shingle) = &self.shingle {
+            if !matches!(self.tokenizer, TokenizerKind::Unicode) {
+                return Err(ValidationError::new(
+                    "shingle is only supported for unicode tokenizer",
+                ));
+            }
+            shingle.validate().map_err(ValidationError::new)?;
+        }
+        if !self.is_trained() {
+            if self.grow_vocabulary {
+                return Err(ValidationError::new(
+                    "grow_vocabulary is only supported for unicode, cjk and ngram tokenizer",
+                ));
+            }
+            return Ok(());
+        }
+        if self.grow_vocabulary {
+            // the vocabulary may be trained on a table first
+            if self.table.is_some() != self.column.is_some() {
+                return Err(ValidationError::new(
+                    "table and column must be given together",
+                ));
+            }
+            return Ok(());
+        }
+        if self.table.is_none() {
+            return Err(ValidationError::new(
+                "table is required for unicode, cjk and ngram tokenizer",
+            ));
+        }
+        if self.column.is_none() {
+            return Err(ValidationError::new(
+                "column is required for unicode, cjk and ngram tokenizer",
+            ));
+        }
+        Ok(())
+    }
+}
+
+extension_sql_file!(
+    "../sql/tokenizer.sql",
+    name = "tokenizer_table",
+    requires = [unicode_tokenizer_split, unicode_tokenizer_split_with]
+);
+
+#[pgrx::pg_extern(requires = ["tokenizer_table"])]
+pub fn create_tokenizer(tokenizer_name: &str, config_str: &str) {
+    create(tokenizer_name, config_str, None);
+}
+
+// create a HuggingFace tokenizer with the content of its `tokenizer.json`
+#[pgrx::pg_extern(name = "create_tokenizer", requires = ["tokenizer_table"])]
+pub fn create_tokenizer_with_definition(tokenizer_name: &str, config_str: &str, definition: &str) {
+    create(tokenizer_name, config_str, Some(definition));
+}
+
+fn create(tokenizer_name: &str, config_str: &str, definition: Option<&str>) {
+    if let Err(e) = validate_tokenizer_name(tokenizer_name) {
+        panic!("Invalid tokenizer name: {}, Details: {}", tokenizer_name, e);
+    }
+
+    let config: TokenizerConfig = toml::from_str(config_str).unwrap_or_report();
+    if let Err(e) = config.validate() {
+        panic!("Invalid tokenizer config, Details: {}", e);
+    }
+    config.analyzer();
+    match (config.tokenizer, definition) {
+        (TokenizerKind::HuggingFace, Some(definition)) => {
+            huggingface::parse(definition);
+        }
+        (TokenizerKind::HuggingFace, None) => {
+            panic!("A definition is required for HuggingFace tokenizer")
+        }
+        (_, Some(_)) => panic!("A definition is only supported for HuggingFace tokenizer"),
+        (_, None) => {}
+    }
+
+    pgrx::Spi::connect(|mut client| {
+        let version = resolve_version(&mut client, tokenizer_name, &config, definition);
+        let query =
+            "INSERT INTO bm25_catalog.tokenizers (name, config, version) VALUES ($1, $2, $3)";
+        let args = Some(vec![
+            (
+                pgrx::PgBuiltInOids::TEXTOID.oid(),
+                tokenizer_name.into_datum(),
+            ),
+            (pgrx::PgBuiltInOids::TEXTOID.oid(), config_str.into_datum()),
+            (pgrx::PgBuiltInOids::INT4OID.oid(), version.into_datum()),
+        ]);
+        client.update(query, None, args).unwrap_or_report();
+        if let Some(definition) = definition {
+            let query = "INSERT INTO bm25_catalog.huggingface_tokenizers (name, definition) VALUES ($1, $2)";
+            let args = Some(vec![
+                (
+                    pgrx::PgBuiltInOids::TEXTOID.oid(),
+                    tokenizer_name.into_datum(),
+                ),
+                (pgrx::PgBuiltInOids::TEXTOID.oid(), definition.into_datum()),
+            ]);
+            client.update(query, None, args).unwrap_or_report();
+        }
+        if config.is_trained() {
+            create_unicode_tokenizer_table(&mut client, tokenizer_name, &config);
+            create_vocab_invalidate_trigger(&mut client, tokenizer_name);
+        }
+    });
+}
+
+// A name and a version must always mean the same config, since the immutable `tokenize` pins
+// them. So every config a tokenizer is created with is recorded in `tokenizer_versions` by its
+// fingerprint, and kept after `drop_tokenizer`. Without a `version` in the config, a config that's
+// recorded gets its old version, and a new one gets the version after all the recorded ones.
+// A `version` in the config that's recorded with another config raises an error.
+fn resolve_version(
+    client: &mut SpiClient<'_>,
+    tokenizer_name: &str,
+    config: &TokenizerConfig,
+    definition: Option<&str>,
+) -> i32 {
+    // the config is normalized by serializing it, so that its formatting doesn't matter
+    let mut normalized = toml::to_string(&TokenizerConfig {
+        version: None,
+        ..config.clone()
+    })
+    .unwrap_or_report();
+    if let Some(definition) = definition {
+        normalized.push_str(definition);
+    }
+    let query = "SELECT encode(sha256(convert_to($1, 'UTF8')), 'hex')";
+    let args = Some(vec![(
+        pgrx::PgBuiltInOids::TEXTOID.oid(),
+        normalized.into_datum(),
+    )]);
+    let fingerprint: String = client
+        .select(query, None, args)
+        .unwrap_or_report()
+        .first()
+        .get_one()
+        .unwrap_or_report()
+        .expect("no fingerprint");
+
+    let query = "SELECT version, fingerprint FROM bm25_catalog.tokenizer_versions WHERE name = $1";
+    let args = Some(vec![(
+        pgrx::PgBuiltInOids::TEXTOID.oid(),
+        tokenizer_name.into_datum(),
+    )]);
+    let recorded = client
+        .select(query, None, args)
+        .unwrap_or_report()
+        .map(|row| {
+            let version: i32 = row.get(1).unwrap_or_report().expect("no version");
+            let fingerprint: String = row.get(2).unwrap_or_report().expect("no fingerprint");
+            (version, fingerprint)
+        })
+        .collect::<Vec<_>>();
+
+    let version = match config.version {
+        Some(version) => {
+            let version = version as i32;
+            if recorded
+                .iter()
+                .any(|(v, f)| *v == version && *f != fingerprint)
+            {
+                pgrx::error!(
+                    "version {} of tokenizer \"{}\" was created with another config, please bump the version",
+                    version,
+                    tokenizer_name
+                );
+            }
+            version
+        }
+        None => recorded
+            .iter()
+            .filter(|(_, f)| *f == fingerprint)
+            .map(|(v, _)| *v)
+            .max()
+            .unwrap_or_else(|| recorded.iter().map(|(v, _)| v + 1).max().unwrap_or(1)),
+    };
+
+    let query = "INSERT INTO bm25_catalog.tokenizer_versions (name, version, fingerprint) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING";
+    let args = Some(vec![
+        (
+            pgrx::PgBuiltInOids::TEXTOID.oid(),
+            tokenizer_name.into_datum(),
+        ),
+        (pgrx::PgBuiltInOids::INT4OID.oid(), version.into_datum()),
+        (pgrx::PgBuiltInOids::TEXTOID.oid(), fingerprint.into_datum()),
+    ]);
+    client.update(query, None, args).unwrap_or_report();
+    version
+}
+
+#[pgrx::pg_extern(requires = ["tokenizer_table"])]
+fn drop_tokenizer(tokenizer_name: &str) {
+    if let Err(e) = validate_tokenizer_name(tokenizer_name) {
+        panic!("Invalid tokenizer name: {}, Details: {}", tokenizer_name, e);
+    }
+
+    pgrx::Spi::connect(|mut client| {
+        let config = read_config(&client, tokenizer_name);
+        if config.is_trained() {
+            let table_name = format!("bm25_catalog.\"{}\"", tokenizer_name);
+            let drop_table = format!("DROP TABLE IF EXISTS {}", table_name);
+            client.update(&drop_table, None, None).unwrap_or_report();
+            if let Some(table) = config.table {
+                let drop_trigger = format!(
+                    "DROP TRIGGER IF EXISTS \"{}_trigger\" ON {}",
+                    tokenizer_name, table
+                );
+                client.update(&drop_trigger, None, None).unwrap_or_report();
+            }
+        }
+
+        let query = "DELETE FROM bm25_catalog.tokenizers WHERE name = $1";
+        let args = Some(vec![(
+            pgrx::PgBuiltInOids::TEXTOID.oid(),
+            tokenizer_name.into_datum(),
+        )]);
+        client.update(query, None, args).unwrap_or_report();
+    });
+}
+
+// Any change of the tokenizer catalog drops the cache of all backends, including a direct
+// `UPDATE` or `DELETE` of it besides `create_tokenizer` and `drop_tokenizer`.
+#[pg_trigger]
+fn tokenizer_catalog_invalidate_trigger<'a>(
+    _trigger: &'a pgrx::PgTrigger<'a>,
+) -> Result<Option<PgHeapTuple<'a, AllocatedByPostgres>>, ()> {
+    cache::invalidate();
+    Ok(None)
+}
+
+// An insert into the vocabulary of a tokenizer drops the tokens cached as missing in all backends,
+// any other change of it drops the whole cache, see `cache`.
+#[pg_trigger]
+fn tokenizer_vocab_invalidate_trigger<'a>(
+    trigger: &'a pgrx::PgTrigger<'a>,
+) -> Result<Option<PgHeapTuple<'a, AllocatedByPostgres>>, ()> {
+    match trigger.op().expect("no trigger operation") {
+        PgTriggerOperation::Insert => {
+            cache::invalidate_missing(trigger.relid().expect("no trigger relation"))
+        }
+        _ => cache::invalidate(),
+    }
+    Ok(None)
+}
+
+// it's created after the vocabulary is filled, so the initial tokens don't fire it
+fn create_vocab_invalidate_trigger(client: &mut SpiClient<'_>, name: &str) {
+    let trigger = format!(
+        r#"
+        CREATE TRIGGER "{}_invalidate_trigger"
+        AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog."{}"
+        FOR EACH STATEMENT EXECUTE FUNCTION bm25_catalog.tokenizer_vocab_invalidate_trigger();
+        "#,
+        name, name
+    );
+    client.update(&trigger, None, None).unwrap_or_report();
+}
+
+extension_sql!(
+    r#"
+CREATE TRIGGER tokenizers_invalidate_trigger
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog.tokenizers
+FOR EACH STATEMENT EXECUTE FUNCTION tokenizer_catalog_invalidate_trigger();
+
+CREATE TRIGGER huggingface_tokenizers_invalidate_trigger
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON bm25_catalog.huggingface_tokenizers
+FOR EACH STATEMENT EXECUTE FUNCTION tokenizer_catalog_invalidate_trigger();
+"#,
+    name = "tokenizer_catalog_invalidate",
+    requires = ["tokenizer_table", tokenizer_catalog_invalidate_trigger]
+);
+
+const TOKENIZER_RESERVED_NAMES: [&[u8]; 5] = [
+    b"Bert",
+    b"Tocken",
+    b"tokenizers",
+    b"huggingface_tokenizers",
+    b"tokenizer_versions",
+];
+
+// 1. It only contains ascii letters, numbers, and underscores.
+// 2. It starts with a letter.
+// 3. Its length cannot exceed NAMEDATALEN - 1
+// 4. It is not a reserved name.
+fn validate_tokenizer_name(name: &str) -> Result<(), String> {
+    let name = name.as_bytes();
+    for &b in name {
+        if !b.is_ascii_alphanumeric() && b != b'_' {
+            return Err(format!("Invalid character: {}", b as char));
+        }
+    }
+    if !(1..=pgrx::pg_sys::NAMEDATALEN as usize - 1).contains(&name.len()) {
+        return Err(format!(
+            "Name length must be between 1 and {}",
+            pgrx::pg_sys::NAMEDATALEN - 1
+        ));
+    }
+    if !name[0].is_ascii_alphabetic() {
+        return Err("Name must start with a letter".to_string());
+    }
+    if TOKENIZER_RESERVED_NAMES.contains(&name) {
+        return Err("The name is reserved, please choose another name".to_string());
+    }
+
+    Ok(())
+}
+
+// 1. create word table
+// 2. scan the text and split it into words and insert them into the word table
+// 3. create a trigger to insert new words into the word table, unless `tokenize` grows it
+fn create_unicode_tokenizer_table(
+    client: &mut SpiClient<'_>,
+    name: &str,
+    config: &TokenizerConfig,
+) {
+    let table_name = format!("bm25_catalog.\"{}\"", name);
+
+    let create_table = format!(
+        r#"
+        CREATE TABLE {} (
+            id int GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+            token TEXT NOT NULL UNIQUE
+        );
+        "#,
+        table_name
+    );
+    client.update(&create_table, None, None).unwrap_or_report();
+
+    let (Some(target_table), Some(column)) = (&config.table, &config.column) else {
+        return;
+    };
+    let select_text = format!("SELECT {} FROM {}", column, target_table);
+    let rows = client.select(&select_text, None, None).unwrap_or_report();
+    let analyzer = config.analyzer();
+    let mut tokens = HashSet::new();
+    for row in rows {
+        let text: &str = row.get(1).unwrap_or_report().expect("no text value");
+        let words = config.split(analyzer.as_ref(), text);
+        tokens.extend(words);
+    }
+
+    let insert_text = format!(
+        r#"
+        INSERT INTO {} (token) VALUES ($1)
+        "#,
+        table_name
+    );
+    for token in tokens {
+        let args = Some(vec![(
+            pgrx::PgBuiltInOids::TEXTOID.oid(),
+            token.into_datum(),
+        )]);
+        client.update(&insert_text, None, args).unwrap_or_report();
+    }
+
+    if config.grow_vocabulary {
+        return;
+    }
+    let trigger = format!(
+        r#"
+        CREATE TRIGGER "{}_trigger"
+        BEFORE INSERT OR UPDATE OF {}
+        ON {}
+        FOR EACH ROW
+        EXECUTE FUNCTION unicode_tokenizer_insert_trigger('{}', '{}');
+        "#,
+        name, column, target_table, name, column
+    );
+    client.update(&trigger, None, None).unwrap_or_report();
+}
+
+#[pgrx::pg_extern(stable, strict, parallel_safe, requires = ["tokenizer_table"])]
+pub fn tokenize(content: &str, tokenizer_name: &str) -> Bm25VectorOutput {
+    tokenize_with(content, tokenizer_name, false)
+}
+
+// tokenize a document and add its new tokens to the vocabulary of a tokenizer with
+// `grow_vocabulary`, it's volatile and parallel unsafe since it writes the vocabulary
+#[pgrx::pg_extern(volatile, strict, parallel_unsafe, requires = ["tokenizer_table"])]
+pub fn tokenize_and_grow(content: &str, tokenizer_name: &str) -> Bm25VectorOutput {
+    tokenize_with(content, tokenizer_name, true)
+}
+
+// the version of the builtin tokenizers, bumped when their output changes
+const BUILTIN_TOKENIZER_VERSION: i32 = 1;
+
+// Tokenize with the given version of a tokenizer, it raises an error if the tokenizer is at
+// another version. It's immutable, so it can be used in expression indexes and generated columns.
+#[pgrx::pg_extern(
+    name = "tokenize",
+    immutable,
+    strict,
+    parallel_safe,
+    requires = ["tokenizer_table"]
+)]
+pub fn tokenize_versioned(content: &str, tokenizer_name: &str, version: i32) -> Bm25VectorOutput {
+    if matches!(tokenizer_name, "Bert" | "Tocken") {
+        check_version(tokenizer_name, BUILTIN_TOKENIZER_VERSION, version);
+        return tokenize_with(content, tokenizer_name, false);
+    }
+    let term_ids = cache::with_tokenizer(tokenizer_name, |tokenizer| {
+        // the vocabulary of a trained tokenizer changes with the data
+        if tokenizer.config.is_trained() {
+            panic!("unicode, cjk and ngram tokenizer cannot be used with a version");
+        }
+        // only the version is checked, `resolve_version` keeps the config of a name and version
+        check_version(tokenizer_name, tokenizer.config.version() as i32, version);
+        encode(tokenizer, tokenizer_name, content, false)
+    });
+    Bm25VectorOutput::from_ids(&term_ids)
+}
+
+// the version of a tokenizer, recorded in the indexes built with it
+pub fn tokenizer_version(tokenizer_name: &str) -> u32 {
+    match tokenizer_name {
+        "Bert" | "Tocken" => BUILTIN_TOKENIZER_VERSION as u32,
+        _ => cache::with_tokenizer(tokenizer_name, |tokenizer| tokenizer.config.version()),
+    }
+}
+
+fn check_version(tokenizer_name: &str, current: i32, version: i32) {
+    if current != version {
+        pgrx::error!(
+            "tokenizer \"{}\" is at version {}, but version {} is requested",
+            tokenizer_name,
+            current,
+            version
+        );
+    }
+}
+
+fn tokenize_with(content: &str, tokenizer_name: &str, grow: bool) -> Bm25VectorOutput {
+    let term_ids = match tokenizer_name {
+        "Bert" => BERT_TOKENIZER.encode(content, Language::English),
+        "Tocken" => TOCKENIZER.encode(content),
+        _ => custom_tokenize(content, tokenizer_name, grow),
+    };
+    Bm25VectorOutput::from_ids(&term_ids)
+}
+
+// decode the ids of a vector, an id unknown to the tokenizer has a NULL token
+#[pgrx::pg_extern(stable, strict, parallel_safe, requires = ["tokenizer_table"])]
+pub fn bm25vector_to_tokens(
+    vector: Bm25VectorInput,
+    tokenizer_name: &str,
+) -> TableIterator<'static, (name!(id, i64), name!(token, Option<String>), name!(tf, i64))> {
+    let vector = vector.borrow();
+    let ids = vector.indexes();
+    let tokens = match tokenizer_name {
+        "Bert" => ids
+            .iter()
+            .map(|&id| BERT_TOKENIZER.0.id_to_token(id))
+            .collect(),
+        "Tocken" => ids.iter().map(|&id| TOCKENIZER.decode(id)).collect(),
+        _ => custom_decode(ids, tokenizer_name),
+    };
+    let rows = ids
+        .iter()
+        .zip(vector.values())
+        .zip(tokens)
+        .map(|((&id, &tf), token)| (id as i64, token, tf as i64))
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+fn read_config(client: &SpiClient<'_>, tokenizer_name: &str) -> TokenizerConfig {
+    let query = "SELECT config, version FROM bm25_catalog.tokenizers WHERE name = $1";
+    let args = Some(vec![(
+        pgrx::PgBuiltInOids::TEXTOID.oid(),
+        tokenizer_name.into_datum(),
+    )]);
+    let mut rows = client.select(query, None, args).unwrap_or_report();
+    if rows.len() != 1 {
+        panic!("Tokenizer not found");
+    }
+
+    let row = rows.next().unwrap();
+    let config: &str = row
+        .get(1)
+        .expect("no config value")
+        .expect("no config value");
+    let mut config: TokenizerConfig = toml::from_str(config).unwrap_or_report();
+    // the version is NULL for the tokenizers created before it's resolved
+    let version: Option<i32> = row.get(2).unwrap_or_report();
+    if let Some(version) = version {
+        config.version = Some(version as u32);
+    }
+    config
+}
+
+fn custom_tokenize(text: &str, tokenizer_name: &str, grow: bool) -> Vec<u32> {
+    cache::with_tokenizer(tokenizer_name, |tokenizer| {
+        encode(tokenizer, tokenizer_name, text, grow)
+    })
+}
+
+fn encode(
+    tokenizer: &mut cache::Tokenizer,
+    tokenizer_name: &str,
+    text: &str,
+    grow: bool,
+) -> Vec<u32> {
+    let config = &tokenizer.config;
+    let analyzer = tokenizer.analyzer.as_ref();
+    match config.tokenizer {
+        TokenizerKind::Bert => match analyzer {
+            Some(analyzer) => BERT_TOKENIZER.encode_with(analyzer, text),
+            None => BERT_TOKENIZER.encode(text, config.language()),
+        },
+        TokenizerKind::Tocken => TOCKENIZER.encode(text),
+        TokenizerKind::Unicode | TokenizerKind::Cjk | TokenizerKind::Ngram => {
+            let grow = grow && config.grow_vocabulary;
+            let tokens = config.split(analyzer, text);
+            tokenizer.lookup(tokenizer_name, tokens, grow)
+        }
+        TokenizerKind::HuggingFace => {
+            let huggingface = tokenizer.huggingface.as_ref().unwrap();
+            huggingface::encode(huggingface, analyzer, text)
+        }
+    }
+}
+
+fn custom_decode(ids: &[u32], tokenizer_name: &str) -> Vec<Option<String>> {
+    cache::with_tokenizer(tokenizer_name, |tokenizer| {
+        match tokenizer.config.tokenizer {
+            TokenizerKind::Bert => ids
+                .iter()
+                .map(|&id| BERT_TOKENIZER.0.id_to_token(id))
+                .collect(),
+            TokenizerKind::Tocken => ids.iter().map(|&id| TOCKENIZER.decode(id)).collect(),
+            TokenizerKind::Unicode | TokenizerKind::Cjk | TokenizerKind::Ngram => {
+                let tokens = cache::select_tokens(tokenizer_name, ids);
+                ids.iter().map(|id| tokens.get(id).cloned()).collect()
+            }
+            TokenizerKind::HuggingFace => {
+                let huggingface = tokenizer.huggingface.as_ref().unwrap();
+                ids.iter().map(|&id| huggingface.id_to_token(id)).collect()
+            }
+        }
+    })
+}
+
+#[pg_trigger]
+fn unicode_tokenizer_set_target_column_trigger<'a>(
+    trigger: &'a pgrx::PgTrigger<'a>,
+) -> Result<Option<PgHeapTuple<'a, impl WhoAllocated>>, ()> {
+    let mut new = trigger.new().expect("new tuple is missing").into_owned();
+    let tg_argv = trigger.extra_args().expect("trigger arguments are missing");
+    if tg_argv.len() != 3 {
+        panic!("Invalid trigger arguments");
+    }
+    let tokenizer_name = &tg_argv[0];
+    let source_column = &tg_argv[1];
+    let target_column = &tg_argv[2];
+
+    let source = new
+        .get_by_name::<&str>(source_column)
+        .expect("source column is missing");
+    let Some(source) = source else {
+        return Ok(Some(new));
+    };
+
+    let target = tokenize(source, tokenizer_name);
+    new.set_by_name(target_column, target)
+        .expect("set target column failed");
+    Ok(Some(new))
+}
@@ -0,0 +1,31 @@
+// Character n-grams match a part of a word, e.g. with `min_gram = 2` and `max_gram = 3`,
+// `sku42` becomes `sk`, `ku`, `u4`, `42`, `sku`, `ku4` and `u42`.
+
+pub const DEFAULT_MIN_GRAM: usize = 2;
+pub const DEFAULT_MAX_GRAM: usize = 3;
+
+// lowercase runs of letters and digits
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// a word shorter than `min_gram` is kept as it is
+pub fn ngram_split(words: Vec<String>, min_gram: usize, max_gram: usize) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in words {
+        let chars = word.chars().collect::<Vec<_>>();
+        if chars.len() < min_gram {
+            tokens.push(word);
+            continue;
+        }
+        for n in min_gram..=max_gram.min(chars.len()) {
+            for gram in chars.windows(n) {
+                tokens.push(gram.iter().collect());
+            }
+        }
+    }
+    tokens
+}
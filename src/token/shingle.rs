@@ -0,0 +1,52 @@
+// Shingles join adjacent words into extra tokens, e.g. with `size = 3`, `full text search`
+// adds `full_text`, `text_search` and `full_text_search`. Postings don't store positions,
+// so it's a cheap way to score higher the documents where the query words appear together.
+
+use serde::{Deserialize, Serialize};
+
+pub const SEPARATOR: &str = "_";
+pub const MAX_SIZE: usize = 5;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShingleConfig {
+    // the maximum number of words in a shingle, from 2 to `MAX_SIZE`
+    #[serde(default = "ShingleConfig::default_size")]
+    pub size: usize,
+    // every shingle is counted `weight` times
+    #[serde(default = "ShingleConfig::default_weight")]
+    pub weight: u32,
+}
+
+impl ShingleConfig {
+    fn default_size() -> usize {
+        2
+    }
+
+    fn default_weight() -> u32 {
+        1
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(2..=MAX_SIZE).contains(&self.size) {
+            return Err("shingle size must be between 2 and 5");
+        }
+        if self.weight == 0 {
+            return Err("shingle weight must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn shingles(&self, words: &[String]) -> Vec<String> {
+        let mut tokens = Vec::new();
+        for n in 2..=self.size {
+            for window in words.windows(n) {
+                let shingle = window.join(SEPARATOR);
+                for _ in 0..self.weight {
+                    tokens.push(shingle.clone());
+                }
+            }
+        }
+        tokens
+    }
+}
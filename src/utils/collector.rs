@@ -0,0 +1,218 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use super::topk_computer::TopKComputer;
+
+// Documents are ranked by score descending, then by id ascending to break ties.
+// Results are sorted from the lowest ranked to the highest ranked one, so they can be popped.
+pub fn rank_order(a: &(f32, u32), b: &(f32, u32)) -> Ordering {
+    a.0.total_cmp(&b.0).then(b.1.cmp(&a.1))
+}
+
+// Receive scored documents from a search, `threshold` tells the search which scores can be skipped.
+pub trait Collector {
+    fn push(&mut self, score: f32, id: u32);
+    fn threshold(&self) -> f32;
+}
+
+impl Collector for TopKComputer {
+    fn push(&mut self, score: f32, id: u32) {
+        TopKComputer::push(self, score, id);
+    }
+
+    fn threshold(&self) -> f32 {
+        TopKComputer::threshold(self)
+    }
+}
+
+// Keep all elements with score greater than a fixed threshold.
+pub struct ThresholdCollector {
+    results: Vec<(f32, u32)>,
+    threshold: f32,
+}
+
+impl ThresholdCollector {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            results: Vec::new(),
+            threshold,
+        }
+    }
+
+    pub fn into_ranked(self) -> RankedResults {
+        RankedResults(BinaryHeap::from(
+            self.results.into_iter().map(Ranked).collect::<Vec<_>>(),
+        ))
+    }
+}
+
+// The elements are popped from the highest ranked to the lowest ranked one, see `rank_order`.
+// The heap is built in linear time, so the first element is returned without sorting the others,
+// and a scan stopped early by a `LIMIT` never ranks the rest.
+pub struct RankedResults(BinaryHeap<Ranked>);
+
+impl RankedResults {
+    pub fn pop(&mut self) -> Option<(f32, u32)> {
+        self.0.pop().map(|Ranked(element)| element)
+    }
+}
+
+struct Ranked((f32, u32));
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        rank_order(&self.0, &other.0)
+    }
+}
+
+impl Collector for ThresholdCollector {
+    fn push(&mut self, score: f32, id: u32) {
+        if score > self.threshold {
+            self.results.push((score, id));
+        }
+    }
+
+    fn threshold(&self) -> f32 {
+        self.threshold
+    }
+}
+
+// Pass on only the elements ranked after a cursor, which is the last element of a previous page.
+// The elements tied with the cursor are held back, since the cursor is only known by its score
+// and its ctid, which is read for the ties alone. `finish` passes on those after the cursor.
+pub struct AfterCollector<'a, C> {
+    inner: &'a mut C,
+    score: f32,
+    ties: Vec<u32>,
+}
+
+impl<'a, C: Collector> AfterCollector<'a, C> {
+    pub fn new(inner: &'a mut C, score: f32) -> Self {
+        Self {
+            inner,
+            score,
+            ties: Vec::new(),
+        }
+    }
+
+    pub fn ties(&self) -> &[u32] {
+        &self.ties
+    }
+
+    pub fn finish(self, mut is_after: impl FnMut(u32) -> bool) {
+        for id in self.ties {
+            if is_after(id) {
+                self.inner.push(self.score, id);
+            }
+        }
+    }
+}
+
+impl<C: Collector> Collector for AfterCollector<'_, C> {
+    fn push(&mut self, score: f32, id: u32) {
+        if score > self.score {
+            return;
+        }
+        if score == self.score {
+            self.ties.push(id);
+            return;
+        }
+        self.inner.push(score, id);
+    }
+
+    // the cursor is an upper bound of the scores, it cannot raise the pruning threshold
+    fn threshold(&self) -> f32 {
+        self.inner.threshold()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_collector() {
+        let mut collector = ThresholdCollector::new(0.5);
+        let mut reference = Vec::new();
+        for id in 0..10000 {
+            let score = rand::random::<f32>();
+            collector.push(score, id);
+            if score > 0.5 {
+                reference.push((score, id));
+            }
+        }
+        reference.sort_unstable_by(rank_order);
+        let mut ranked = collector.into_ranked();
+        let popped = std::iter::from_fn(|| ranked.pop()).collect::<Vec<_>>();
+        reference.reverse();
+        assert_eq!(popped, reference);
+    }
+
+    #[test]
+    fn test_topk_computer_with_threshold() {
+        let mut topk = TopKComputer::with_threshold(10, 0.9);
+        for id in 0..10000 {
+            topk.push(rand::random::<f32>(), id);
+        }
+        let results = topk.to_sorted_slice();
+        assert!(results.len() <= 10);
+        assert!(results.iter().all(|&(score, _)| score > 0.9));
+    }
+
+    #[test]
+    fn test_after_collector() {
+        let scores = [3.0, 2.0, 2.0, 2.0, 1.0];
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut topk = TopKComputer::new(2);
+            match cursor {
+                Some((score, cursor_id)) => {
+                    let mut after = AfterCollector::new(&mut topk, score);
+                    for (id, &score) in scores.iter().enumerate() {
+                        after.push(score, id as u32);
+                    }
+                    assert!(after.ties().contains(&cursor_id));
+                    after.finish(|id| id > cursor_id);
+                }
+                None => {
+                    for (id, &score) in scores.iter().enumerate() {
+                        topk.push(score, id as u32);
+                    }
+                }
+            }
+            let page = topk
+                .to_sorted_slice()
+                .iter()
+                .rev()
+                .copied()
+                .collect::<Vec<_>>();
+            let Some(&last) = page.last() else {
+                break;
+            };
+            cursor = Some(last);
+            pages.push(page);
+        }
+        assert_eq!(
+            pages,
+            vec![
+                vec![(3.0, 0), (2.0, 1)],
+                vec![(2.0, 2), (2.0, 3)],
+                vec![(1.0, 4)],
+            ]
+        );
+    }
+}
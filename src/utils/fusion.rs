@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    // (s - min) / (max - min)
+    MinMax,
+    // (s - mean) / standard deviation
+    ZScore,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "minmax" => Some(Self::MinMax),
+            "zscore" => Some(Self::ZScore),
+            _ => None,
+        }
+    }
+}
+
+pub fn normalize(scores: &mut [f32], method: Normalization) {
+    if scores.is_empty() {
+        return;
+    }
+    match method {
+        Normalization::MinMax => {
+            let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let range = max - min;
+            for s in scores.iter_mut() {
+                *s = if range > 0.0 { (*s - min) / range } else { 1.0 };
+            }
+        }
+        Normalization::ZScore => {
+            let n = scores.len() as f32;
+            let mean = scores.iter().sum::<f32>() / n;
+            let variance = scores.iter().map(|s| (s - mean) * (s - mean)).sum::<f32>() / n;
+            let std = variance.sqrt();
+            for s in scores.iter_mut() {
+                *s = if std > 0.0 { (*s - mean) / std } else { 0.0 };
+            }
+        }
+    }
+}
+
+// Accumulate the contribution of each list, only the first occurrence of a document in a list counts.
+// Results are sorted by score in descending order, ties keep the order of first appearance.
+fn fuse<T: Copy + Eq + Hash>(
+    lists: impl Iterator<Item = impl Iterator<Item = (T, f32)>>,
+) -> Vec<(T, f32)> {
+    let mut results: Vec<(T, f32)> = Vec::new();
+    let mut positions = HashMap::new();
+    for list in lists {
+        let mut seen = HashSet::new();
+        for (id, score) in list {
+            if !seen.insert(id) {
+                continue;
+            }
+            let position = *positions.entry(id).or_insert_with(|| {
+                results.push((id, 0.0));
+                results.len() - 1
+            });
+            results[position].1 += score;
+        }
+    }
+    results.sort_by(|a, b| a.1.total_cmp(&b.1).reverse());
+    results
+}
+
+// Reciprocal rank fusion, a document at rank r (starting from 1) of a list contributes 1 / (k + r).
+pub fn reciprocal_rank_fusion<T: Copy + Eq + Hash>(lists: &[&[T]], k: f32) -> Vec<(T, f32)> {
+    fuse(lists.iter().map(|list| {
+        list.iter()
+            .enumerate()
+            .map(move |(i, &id)| (id, 1.0 / (k + (i + 1) as f32)))
+    }))
+}
+
+// Weighted sum of the scores of each list, a document missing from a list gets the minimum score
+// of the list from it, as it ranks below every document in the list. With z-score normalization,
+// 0 would be the mean and put it above half of them.
+pub fn weighted_fusion<T: Copy + Eq + Hash>(lists: &[(&[T], &[f32], f32)]) -> Vec<(T, f32)> {
+    let mins = lists
+        .iter()
+        .map(|&(_, scores, _)| scores.iter().copied().reduce(f32::min).unwrap_or(0.0))
+        .collect::<Vec<_>>();
+    // every document gets the minimum of each list, and the lists add what's above it
+    let base = lists
+        .iter()
+        .zip(&mins)
+        .map(|(&(_, _, weight), &min)| weight * min)
+        .sum::<f32>();
+    let mut results = fuse(
+        lists
+            .iter()
+            .zip(&mins)
+            .map(|(&(ids, scores, weight), &min)| {
+                assert_eq!(ids.len(), scores.len());
+                ids.iter()
+                    .zip(scores)
+                    .map(move |(&id, &score)| (id, weight * (score - min)))
+            }),
+    );
+    for (_, score) in results.iter_mut() {
+        *score += base;
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let mut scores = [2.0, 4.0, 6.0];
+        normalize(&mut scores, Normalization::MinMax);
+        assert_eq!(scores, [0.0, 0.5, 1.0]);
+
+        let mut scores = [3.0, 3.0];
+        normalize(&mut scores, Normalization::MinMax);
+        assert_eq!(scores, [1.0, 1.0]);
+
+        let mut scores = [2.0, 4.0, 6.0, 8.0];
+        normalize(&mut scores, Normalization::ZScore);
+        let mean = scores.iter().sum::<f32>() / 4.0;
+        let variance = scores.iter().map(|s| s * s).sum::<f32>() / 4.0;
+        assert!(mean.abs() < 1e-6);
+        assert!((variance - 1.0).abs() < 1e-6);
+        assert!(scores.windows(2).all(|w| w[0] < w[1]));
+
+        let mut scores = [5.0];
+        normalize(&mut scores, Normalization::ZScore);
+        assert_eq!(scores, [0.0]);
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let results = reciprocal_rank_fusion(&[&[1, 2, 3], &[3, 1, 4, 1]], 60.0);
+        let ids = results.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 3, 2, 4]);
+        assert_eq!(results[0].1, 1.0 / 61.0 + 1.0 / 62.0);
+        assert_eq!(results[1].1, 1.0 / 63.0 + 1.0 / 61.0);
+        assert_eq!(results[3].1, 1.0 / 63.0);
+    }
+
+    #[test]
+    fn test_weighted_fusion() {
+        let results = weighted_fusion(&[
+            (&[1, 2, 3], &[1.0, 0.5, 0.0], 0.7),
+            (&[3, 2], &[1.0, 0.0], 0.3),
+        ]);
+        let ids = results.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(results[0].1, 0.7);
+        assert_eq!(results[1].1, 0.35);
+        assert_eq!(results[2].1, 0.3);
+
+        // a document missing from a z-score normalized list ranks below the ones in it
+        let mut bm25_scores = [3.0, 2.0, 1.0];
+        normalize(&mut bm25_scores, Normalization::ZScore);
+        let mut other_scores = [1.0, 0.0];
+        normalize(&mut other_scores, Normalization::ZScore);
+        let results = weighted_fusion(&[
+            (&[1, 2, 3], &bm25_scores, 0.5),
+            (&[3, 4], &other_scores, 0.5),
+        ]);
+        let ids = results.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 3, 2, 4]);
+        assert!((results[2].1 - 0.5 * other_scores[1]).abs() < 1e-6);
+        assert!((results[3].1 - 0.5 * (bm25_scores[2] + other_scores[1])).abs() < 1e-6);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod cells;
+pub mod collector;
 pub mod compress_block;
+pub mod fusion;
 pub mod loser_tree;
 pub mod topk_computer;
 pub mod vint;
@@ -1,3 +1,5 @@
+use super::collector::rank_order;
+
 // Store 2*k elements in a buffer, and truncate to k elements when the buffer is full.
 // Using variant of median selection from quicksort.
 pub struct TopKComputer {
@@ -10,12 +12,17 @@ pub struct TopKComputer {
 // Computer maximum k elements in a stream with positive numbers.
 impl TopKComputer {
     pub fn new(k: usize) -> Self {
+        Self::with_threshold(k, 0.0)
+    }
+
+    // elements with score less than or equal to `threshold` are never kept
+    pub fn with_threshold(k: usize, threshold: f32) -> Self {
         assert!(k > 0);
         Self {
             buffer: vec![(0.0, 0); k * 2].into_boxed_slice(),
             len: 0,
             k,
-            threshold: 0.0,
+            threshold,
         }
     }
 
@@ -25,7 +32,8 @@ impl TopKComputer {
         }
         if self.buffer.len() == self.len {
             let median = self.truncate_top_k();
-            self.threshold = median;
+            // elements tied with the k-th one can still win with a smaller id
+            self.threshold = median.next_down();
         }
         self.buffer[self.len] = (score, id);
         self.len += 1;
@@ -35,19 +43,19 @@ impl TopKComputer {
         self.threshold
     }
 
-    // Return top-k elements in ascending order.
+    // Return top-k elements in ascending order, see `rank_order`.
     pub fn to_sorted_slice(&mut self) -> &[(f32, u32)] {
         if self.len > self.k {
             self.truncate_top_k();
         }
-        self.buffer[..self.len].sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        self.buffer[..self.len].sort_unstable_by(rank_order);
         &self.buffer[..self.len]
     }
 
     fn truncate_top_k(&mut self) -> f32 {
         let (_, median, _) = self
             .buffer
-            .select_nth_unstable_by(self.k, |a, b| a.0.total_cmp(&b.0).reverse());
+            .select_nth_unstable_by(self.k, |a, b| rank_order(b, a));
         self.len = self.k;
         median.0
     }
@@ -115,4 +123,17 @@ mod tests {
             assert_eq!(a.1, b.1);
         }
     }
+
+    #[test]
+    fn test_topk_computer_ties() {
+        let mut topk = TopKComputer::new(10);
+        let mut ids = (0..1000).collect::<Vec<u32>>();
+        ids.shuffle(&mut rand::thread_rng());
+        for id in ids {
+            topk.push(1.0, id);
+        }
+        let results = topk.to_sorted_slice();
+        let expected = (0..10).rev().map(|id| (1.0, id)).collect::<Vec<_>>();
+        assert_eq!(results, expected);
+    }
 }
@@ -128,9 +128,9 @@ mod test {
             reference.push(val);
         }
         let mut data = buf.as_slice();
-        for i in 0..100000 {
+        for &expected in reference.iter() {
             let val = decode_vint32(&mut data);
-            assert_eq!(reference[i], val);
+            assert_eq!(expected, val);
         }
     }
 
@@ -158,9 +158,9 @@ mod test {
             reference.push(val);
         }
         let mut data = buf.as_slice();
-        for i in 0..100000 {
+        for &expected in reference.iter() {
             let val = decode_vint64(&mut data);
-            assert_eq!(reference[i], val);
+            assert_eq!(expected, val);
         }
     }
 }
@@ -1,7 +1,7 @@
 use crate::{
     datatype::Bm25VectorBorrowed,
     segment::field_norm::{fieldnorm_to_id, id_to_fieldnorm},
-    segment::term_stat::TermStatReader,
+    segment::term_stat::TermStatRead,
 };
 
 const K1: f32 = 1.2;
@@ -40,7 +40,7 @@ pub fn idf(doc_cnt: u32, doc_freq: u32) -> f32 {
 pub fn bm25_score_batch(
     doc_cnt: u32,
     avgdl: f32,
-    term_stat_reader: &TermStatReader,
+    term_stat_reader: &impl TermStatRead,
     target_vector: Bm25VectorBorrowed,
     query_vector: Bm25VectorBorrowed,
 ) -> f32 {
@@ -72,3 +72,21 @@ pub fn bm25_score_batch(
     }
     scores
 }
+
+// upper bound of `bm25_score_batch` for any target vector, the sum of the max score of each term
+pub fn bm25_max_score(
+    doc_cnt: u32,
+    avgdl: f32,
+    term_stat_reader: &impl TermStatRead,
+    query_vector: Bm25VectorBorrowed,
+) -> f32 {
+    query_vector
+        .indexes()
+        .iter()
+        .zip(query_vector.values())
+        .map(|(&term_id, &count)| {
+            let idf = idf(doc_cnt, term_stat_reader.read(term_id));
+            Bm25Weight::new(count, idf, avgdl).max_score()
+        })
+        .sum()
+}